@@ -0,0 +1,166 @@
+//! Compares the work-stealing `ThreadPool` with the original design, where
+//! every worker blocks on one `Arc<Mutex<mpsc::Receiver<Job>>>`.
+//!
+//! Run with `cargo bench`. The numbers only mean something on a machine with
+//! several cores: on a single core every wakeup is a context switch and the
+//! extra bookkeeping of the deques cannot pay for itself.
+
+use std::{
+    hint::black_box,
+    sync::{atomic::{AtomicUsize, Ordering}, mpsc, Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use server::ThreadPool;
+
+const WORKERS: usize = 8;
+const JOBS: usize = 200_000;
+const ROUNDS: usize = 5;
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+/// The pool as it was before per-worker deques.
+struct MutexPool {
+    sender: Option<mpsc::Sender<Job>>,
+    workers: Vec<thread::JoinHandle<()>>,
+}
+
+impl MutexPool {
+    fn new(size: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+
+        let workers = (0..size)
+            .map(|_| {
+                let receiver = Arc::clone(&receiver);
+                thread::spawn(move || {
+                    loop {
+                        let message = receiver.lock().unwrap().recv();
+                        match message {
+                            Ok(job) => job(),
+                            Err(_) => break,
+                        }
+                    }
+                })
+            })
+            .collect();
+
+        MutexPool { sender: Some(sender), workers }
+    }
+
+    fn execute<F>(&self, f: F)
+    where F: FnOnce() + Send + 'static {
+        self.sender.as_ref().unwrap().send(Box::new(f)).unwrap();
+    }
+}
+
+impl Drop for MutexPool {
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        for worker in self.workers.drain(..) {
+            worker.join().unwrap();
+        }
+    }
+}
+
+fn short_job(counter: &AtomicUsize) {
+    let mut x = black_box(0u64);
+    for i in 0..100 {
+        x = x.wrapping_mul(31).wrapping_add(i);
+    }
+    black_box(x);
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+fn wait_for(counter: &AtomicUsize, jobs: usize) {
+    while counter.load(Ordering::Relaxed) < jobs {
+        thread::yield_now();
+    }
+}
+
+fn mutex_pool_short_jobs() -> Duration {
+    let pool = MutexPool::new(WORKERS);
+    let counter = Arc::new(AtomicUsize::new(0));
+
+    let start = Instant::now();
+    for _ in 0..JOBS {
+        let counter = Arc::clone(&counter);
+        pool.execute(move || short_job(&counter));
+    }
+    wait_for(&counter, JOBS);
+    start.elapsed()
+}
+
+fn thread_pool_short_jobs() -> Duration {
+    let pool = ThreadPool::new(WORKERS);
+    let counter = Arc::new(AtomicUsize::new(0));
+
+    let start = Instant::now();
+    for _ in 0..JOBS {
+        let counter = Arc::clone(&counter);
+        pool.execute(move || short_job(&counter)).unwrap();
+    }
+    wait_for(&counter, JOBS);
+    start.elapsed()
+}
+
+// Each top-level job fans out into more jobs, which the work-stealing pool
+// keeps on the submitting worker's deque.
+fn mutex_pool_fan_out() -> Duration {
+    let pool = Arc::new(MutexPool::new(WORKERS));
+    let counter = Arc::new(AtomicUsize::new(0));
+    let parents = JOBS / 100;
+
+    let start = Instant::now();
+    for _ in 0..parents {
+        let spawner = Arc::clone(&pool);
+        let counter = Arc::clone(&counter);
+        pool.execute(move || {
+            for _ in 0..100 {
+                let counter = Arc::clone(&counter);
+                spawner.execute(move || short_job(&counter));
+            }
+        });
+    }
+    wait_for(&counter, parents * 100);
+    start.elapsed()
+}
+
+fn thread_pool_fan_out() -> Duration {
+    let pool = Arc::new(ThreadPool::new(WORKERS));
+    let counter = Arc::new(AtomicUsize::new(0));
+    let parents = JOBS / 100;
+
+    let start = Instant::now();
+    for _ in 0..parents {
+        let spawner = Arc::clone(&pool);
+        let counter = Arc::clone(&counter);
+        pool.execute(move || {
+            for _ in 0..100 {
+                let counter = Arc::clone(&counter);
+                spawner.execute(move || short_job(&counter)).unwrap();
+            }
+        }).unwrap();
+    }
+    wait_for(&counter, parents * 100);
+    start.elapsed()
+}
+
+fn report(name: &str, run: fn() -> Duration) {
+    let mut best = Duration::MAX;
+    for _ in 0..ROUNDS {
+        best = best.min(run());
+    }
+
+    let jobs_per_sec = JOBS as f64 / best.as_secs_f64();
+    println!("{name:<28} {:>10.2?}  {jobs_per_sec:>14.0} jobs/s", best);
+}
+
+fn main() {
+    println!("{WORKERS} workers, {JOBS} jobs, best of {ROUNDS}");
+    report("short jobs / mutex", mutex_pool_short_jobs);
+    report("short jobs / work-stealing", thread_pool_short_jobs);
+    report("fan-out / mutex", mutex_pool_fan_out);
+    report("fan-out / work-stealing", thread_pool_fan_out);
+}
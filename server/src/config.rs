@@ -0,0 +1,284 @@
+use std::{env, error::Error, fmt, path::PathBuf, time::Duration};
+
+use crate::http::LogFormat;
+
+/// Usage text for the server binary.
+pub const USAGE: &str = "\
+Usage: server [OPTIONS]
+
+Options:
+  -a, --address <ADDR>           Address to bind [env: SERVER_ADDRESS] [default: 127.0.0.1]
+  -p, --port <PORT>              Port to listen on [env: SERVER_PORT] [default: 7878]
+  -w, --workers <N>              Number of worker threads [env: SERVER_WORKERS] [default: 4]
+  -r, --root <DIR>               Document root for static files [env: SERVER_ROOT] [default: public]
+      --shutdown-timeout <SECS>  How long to wait for open connections on shutdown
+                                 [env: SERVER_SHUTDOWN_TIMEOUT] [default: 30]
+      --header-timeout <SECS>    How long a client has to send request headers
+                                 [env: SERVER_HEADER_TIMEOUT] [default: 10]
+      --body-timeout <SECS>      How long a client has to send a request body
+                                 [env: SERVER_BODY_TIMEOUT] [default: 30]
+      --write-timeout <SECS>     How long sending a response may block
+                                 [env: SERVER_WRITE_TIMEOUT] [default: 30]
+      --max-body-size <BYTES>    Largest request body accepted
+                                 [env: SERVER_MAX_BODY_SIZE] [default: 1048576]
+      --max-connections-per-ip <N>
+                                 Open connections allowed per client address
+                                 [env: SERVER_MAX_CONNECTIONS_PER_IP] [default: 8]
+      --access-log <PATH>        File to append the access log to, or - for standard output
+                                 [env: SERVER_ACCESS_LOG] [default: -]
+      --log-format <FORMAT>      Access log format: common, combined or json
+                                 [env: SERVER_LOG_FORMAT] [default: common]
+  -h, --help                     Print this help
+";
+
+/// Settings of the server binary, read from the command line and the environment.
+///
+/// Command-line options take precedence over environment variables, which
+/// take precedence over the defaults.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    pub address: String,
+    pub port: u16,
+    pub workers: usize,
+    pub root: PathBuf,
+    pub shutdown_timeout: Duration,
+    pub header_timeout: Duration,
+    pub body_timeout: Duration,
+    pub write_timeout: Duration,
+    pub max_body_size: u64,
+    /// Enough for the handful of connections a browser opens to one host.
+    pub max_connections_per_ip: usize,
+    /// Where to write the access log; `None` for standard output.
+    pub access_log: Option<PathBuf>,
+    pub log_format: LogFormat,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            address: "127.0.0.1".to_string(),
+            port: 7878,
+            workers: 4,
+            root: PathBuf::from("public"),
+            shutdown_timeout: Duration::from_secs(30),
+            header_timeout: Duration::from_secs(10),
+            body_timeout: Duration::from_secs(30),
+            write_timeout: Duration::from_secs(30),
+            max_body_size: 1024 * 1024,
+            max_connections_per_ip: 8,
+            access_log: None,
+            log_format: LogFormat::Common,
+        }
+    }
+}
+
+/// Error returned when the configuration cannot be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// `--help` was given. Not a failure, but there is nothing to run.
+    HelpRequested,
+    UnknownOption(String),
+    MissingValue(String),
+    InvalidValue { name: String, value: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::HelpRequested => write!(f, "help requested"),
+            ConfigError::UnknownOption(option) => write!(f, "unknown option {option}"),
+            ConfigError::MissingValue(option) => write!(f, "option {option} needs a value"),
+            ConfigError::InvalidValue { name, value } => write!(f, "invalid value {value:?} for {name}"),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+impl Config {
+    /// Reads the configuration from the process arguments and environment.
+    pub fn load() -> Result<Config, ConfigError> {
+        Config::from_sources(env::args().skip(1), |name| env::var(name).ok())
+    }
+
+    /// Reads the configuration from `args`, without the program name, and
+    /// from the environment variables returned by `var`.
+    pub fn from_sources<I, V>(args: I, var: V) -> Result<Config, ConfigError>
+    where
+        I: IntoIterator<Item = String>,
+        V: Fn(&str) -> Option<String>,
+    {
+        let mut config = Config::default();
+
+        for (variable, option) in [
+            ("SERVER_ADDRESS", "--address"),
+            ("SERVER_PORT", "--port"),
+            ("SERVER_WORKERS", "--workers"),
+            ("SERVER_ROOT", "--root"),
+            ("SERVER_SHUTDOWN_TIMEOUT", "--shutdown-timeout"),
+            ("SERVER_HEADER_TIMEOUT", "--header-timeout"),
+            ("SERVER_BODY_TIMEOUT", "--body-timeout"),
+            ("SERVER_WRITE_TIMEOUT", "--write-timeout"),
+            ("SERVER_MAX_BODY_SIZE", "--max-body-size"),
+            ("SERVER_MAX_CONNECTIONS_PER_IP", "--max-connections-per-ip"),
+            ("SERVER_ACCESS_LOG", "--access-log"),
+            ("SERVER_LOG_FORMAT", "--log-format"),
+        ] {
+            if let Some(value) = var(variable) {
+                config.set(option, variable, value)?;
+            }
+        }
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (option, inline) = match arg.split_once('=') {
+                Some((option, value)) if option.starts_with("--") => (option.to_string(), Some(value.to_string())),
+                _ => (arg, None),
+            };
+
+            let option = match option.as_str() {
+                "-h" | "--help" => return Err(ConfigError::HelpRequested),
+                "-a" | "--address" => "--address",
+                "-p" | "--port" => "--port",
+                "-w" | "--workers" => "--workers",
+                "-r" | "--root" => "--root",
+                "--shutdown-timeout" => "--shutdown-timeout",
+                "--header-timeout" => "--header-timeout",
+                "--body-timeout" => "--body-timeout",
+                "--write-timeout" => "--write-timeout",
+                "--max-body-size" => "--max-body-size",
+                "--max-connections-per-ip" => "--max-connections-per-ip",
+                "--access-log" => "--access-log",
+                "--log-format" => "--log-format",
+                _ => return Err(ConfigError::UnknownOption(option)),
+            };
+            let value = inline.or_else(|| args.next()).ok_or_else(|| ConfigError::MissingValue(option.to_string()))?;
+            config.set(option, option, value)?;
+        }
+
+        Ok(config)
+    }
+
+    /// Returns the `address:port` to bind.
+    pub fn bind_address(&self) -> String {
+        if self.address.contains(':') {
+            format!("[{}]:{}", self.address, self.port)
+        } else {
+            format!("{}:{}", self.address, self.port)
+        }
+    }
+
+    /// Applies `value` to the setting of `option`. `name` is what errors call it.
+    fn set(&mut self, option: &str, name: &str, value: String) -> Result<(), ConfigError> {
+        let invalid = || ConfigError::InvalidValue { name: name.to_string(), value: value.clone() };
+
+        match option {
+            "--address" if !value.is_empty() => self.address = value,
+            "--port" => self.port = value.parse().map_err(|_| invalid())?,
+            "--workers" => self.workers = value.parse().ok().filter(|&n| n > 0).ok_or_else(invalid)?,
+            "--root" if !value.is_empty() => self.root = PathBuf::from(value),
+            "--shutdown-timeout" => self.shutdown_timeout = Duration::from_secs(value.parse().map_err(|_| invalid())?),
+            "--header-timeout" => self.header_timeout = seconds(&value).ok_or_else(invalid)?,
+            "--body-timeout" => self.body_timeout = seconds(&value).ok_or_else(invalid)?,
+            "--write-timeout" => self.write_timeout = seconds(&value).ok_or_else(invalid)?,
+            "--max-body-size" => self.max_body_size = value.parse().map_err(|_| invalid())?,
+            "--max-connections-per-ip" => {
+                self.max_connections_per_ip = value.parse().ok().filter(|&n| n > 0).ok_or_else(invalid)?;
+            }
+            "--access-log" if value == "-" => self.access_log = None,
+            "--access-log" if !value.is_empty() => self.access_log = Some(PathBuf::from(value)),
+            "--log-format" => self.log_format = value.parse().map_err(|_| invalid())?,
+            _ => return Err(invalid()),
+        }
+        Ok(())
+    }
+}
+
+/// Parses a whole, non-zero number of seconds.
+fn seconds(value: &str) -> Option<Duration> {
+    value.parse().ok().filter(|&secs| secs > 0).map(Duration::from_secs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(args: &[&str], env: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let env: Vec<(String, String)> = env.iter().map(|&(k, v)| (k.to_string(), v.to_string())).collect();
+        Config::from_sources(args.iter().map(|arg| arg.to_string()), |name| {
+            env.iter().find(|(key, _)| key == name).map(|(_, value)| value.clone())
+        })
+    }
+
+    #[test]
+    fn defaults_without_arguments_or_environment() {
+        assert_eq!(load(&[], &[]), Ok(Config::default()));
+        assert_eq!(Config::default().bind_address(), "127.0.0.1:7878");
+    }
+
+    #[test]
+    fn arguments_override_the_environment() {
+        let config = load(
+            &["--port", "9000", "-w", "8", "--root=site"],
+            &[("SERVER_PORT", "8000"), ("SERVER_ADDRESS", "0.0.0.0"), ("SERVER_SHUTDOWN_TIMEOUT", "5")],
+        )
+        .unwrap();
+
+        assert_eq!(config.bind_address(), "0.0.0.0:9000");
+        assert_eq!(config.workers, 8);
+        assert_eq!(config.root, PathBuf::from("site"));
+        assert_eq!(config.shutdown_timeout, Duration::from_secs(5));
+    }
+
+    #[test]
+    fn reads_connection_limits() {
+        let config = load(
+            &["--header-timeout", "3", "--max-connections-per-ip=3"],
+            &[("SERVER_WRITE_TIMEOUT", "7"), ("SERVER_HEADER_TIMEOUT", "9"), ("SERVER_MAX_BODY_SIZE", "4096")],
+        )
+        .unwrap();
+
+        assert_eq!(config.header_timeout, Duration::from_secs(3));
+        assert_eq!(config.body_timeout, Duration::from_secs(30));
+        assert_eq!(config.write_timeout, Duration::from_secs(7));
+        assert_eq!(config.max_body_size, 4096);
+        assert_eq!(config.max_connections_per_ip, 3);
+        assert!(load(&["--body-timeout", "0"], &[]).is_err());
+        assert!(load(&["--max-connections-per-ip", "0"], &[]).is_err());
+    }
+
+    #[test]
+    fn connections_per_ip_do_not_depend_on_the_workers() {
+        assert_eq!(Config::default().max_connections_per_ip, 8);
+        assert_eq!(load(&["-w", "1"], &[]).unwrap().max_connections_per_ip, 8);
+        assert_eq!(load(&["-w", "64"], &[]).unwrap().max_connections_per_ip, 8);
+        assert_eq!(load(&["-w", "2"], &[("SERVER_MAX_CONNECTIONS_PER_IP", "6")]).unwrap().max_connections_per_ip, 6);
+    }
+
+    #[test]
+    fn reads_access_log_settings() {
+        let config = load(&["--log-format", "json"], &[("SERVER_ACCESS_LOG", "/var/log/server.log")]).unwrap();
+        assert_eq!(config.access_log, Some(PathBuf::from("/var/log/server.log")));
+        assert_eq!(config.log_format, LogFormat::Json);
+
+        assert_eq!(load(&["--access-log=-"], &[("SERVER_ACCESS_LOG", "x.log")]).unwrap().access_log, None);
+        assert!(load(&["--log-format", "xml"], &[]).is_err());
+    }
+
+    #[test]
+    fn rejects_bad_input() {
+        assert_eq!(load(&["--verbose"], &[]), Err(ConfigError::UnknownOption("--verbose".to_string())));
+        assert_eq!(load(&["--port"], &[]), Err(ConfigError::MissingValue("--port".to_string())));
+        assert_eq!(
+            load(&[], &[("SERVER_WORKERS", "0")]),
+            Err(ConfigError::InvalidValue { name: "SERVER_WORKERS".to_string(), value: "0".to_string() })
+        );
+        assert_eq!(load(&["-p", "70000"], &[]).unwrap_err().to_string(), "invalid value \"70000\" for --port");
+        assert_eq!(load(&["-h"], &[]), Err(ConfigError::HelpRequested));
+    }
+
+    #[test]
+    fn ipv6_addresses_are_bracketed() {
+        assert_eq!(load(&["-a", "::1"], &[]).unwrap().bind_address(), "[::1]:7878");
+    }
+}
@@ -0,0 +1,28 @@
+mod access_log;
+mod compression;
+mod connection;
+mod date;
+mod deflate;
+mod error;
+mod headers;
+mod limiter;
+mod middleware;
+mod range;
+mod request;
+mod response;
+mod router;
+mod static_files;
+mod status;
+
+pub use access_log::{AccessLog, LogFormat};
+pub use compression::Compression;
+pub use connection::{serve_connection, ConnectionConfig};
+pub use error::ServerError;
+pub use headers::Headers;
+pub use limiter::{ConnectionLimiter, ConnectionPermit};
+pub use middleware::{CatchPanic, Middleware, Next, RequestId, Timing};
+pub use request::{JsonRejection, Method, ParseError, Request, Version};
+pub use response::{FileBody, Response};
+pub use router::{Handler, Router};
+pub use static_files::StaticFiles;
+pub use status::StatusCode;
@@ -0,0 +1,377 @@
+use std::{
+    fmt::{self, Write as _},
+    fs::OpenOptions,
+    io::{self, Write},
+    net::SocketAddr,
+    path::Path,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant, SystemTime},
+};
+
+use super::{date::DateTime, Method, Middleware, Next, Request, Response};
+
+/// The layout of [`AccessLog`] lines.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LogFormat {
+    /// The Common Log Format:
+    ///
+    /// ```text
+    /// 127.0.0.1 - - [10/Oct/2000:13:55:36 +0000] "GET /index.html HTTP/1.1" 200 2326
+    /// ```
+    #[default]
+    Common,
+    /// The Common Log Format followed by the quoted `Referer` and
+    /// `User-Agent` headers.
+    Combined,
+    /// One JSON object per line, with the fields of the combined format, the
+    /// latency in milliseconds and the `X-Request-Id` of the request, if any.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "common" => Ok(LogFormat::Common),
+            "combined" => Ok(LogFormat::Combined),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("unknown log format {s:?}")),
+        }
+    }
+}
+
+/// A [`Middleware`] that writes a line about every request, in one of the
+/// [`LogFormat`]s.
+///
+/// Add it before other middlewares so that it sees the response they make,
+/// compressed or not, and times all of them. Requests that are refused
+/// before they reach the router, such as malformed or oversized ones, are
+/// only logged if a clone is also given to
+/// [`ConnectionConfig::access_log`](super::ConnectionConfig::access_log).
+/// Clones write to the same place.
+///
+/// ```no_run
+/// use server::http::{AccessLog, LogFormat, Router};
+///
+/// let mut router = Router::new();
+/// router.wrap(AccessLog::file("access.log")?.format(LogFormat::Combined));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Clone)]
+pub struct AccessLog {
+    out: Arc<Mutex<Box<dyn Write + Send>>>,
+    format: LogFormat,
+}
+
+impl fmt::Debug for AccessLog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessLog").field("format", &self.format).finish_non_exhaustive()
+    }
+}
+
+impl AccessLog {
+    /// Logs to `out` in the common format, one write per line.
+    pub fn new<W: Write + Send + 'static>(out: W) -> Self {
+        AccessLog { out: Arc::new(Mutex::new(Box::new(out))), format: LogFormat::default() }
+    }
+
+    pub fn stdout() -> Self {
+        Self::new(io::stdout())
+    }
+
+    /// Appends to the file at `path`, creating it if needed.
+    pub fn file<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self::new(OpenOptions::new().create(true).append(true).open(path)?))
+    }
+
+    pub fn format(mut self, format: LogFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// Logs a response that the connection sent without dispatching, with
+    /// the head of the request if it was read. `started` is when the
+    /// request began to arrive.
+    pub fn log_refused(
+        &self,
+        client: Option<SocketAddr>,
+        request: Option<&Request>,
+        response: &Response,
+        started: Instant,
+    ) {
+        let latency = started.elapsed();
+        self.write(&Entry { request, client, response, received: SystemTime::now() - latency, latency });
+    }
+
+    fn write(&self, entry: &Entry<'_>) {
+        let line = match self.format {
+            LogFormat::Common => entry.common(),
+            LogFormat::Combined => entry.combined(),
+            LogFormat::Json => entry.json(),
+        };
+
+        // A broken log must not break the request, and a panic while another
+        // thread held the lock leaves nothing half-written worth protecting.
+        let mut out = self.out.lock().unwrap_or_else(|e| e.into_inner());
+        let _ = out.write_all(line.as_bytes()).and_then(|()| out.flush());
+    }
+}
+
+impl Middleware for AccessLog {
+    fn handle(&self, request: &mut Request, next: Next<'_>) -> Response {
+        let received = SystemTime::now();
+        let start = Instant::now();
+        let response = next.run(request);
+        self.write(&Entry {
+            request: Some(request),
+            client: request.remote_addr(),
+            response: &response,
+            received,
+            latency: start.elapsed(),
+        });
+        response
+    }
+}
+
+/// What is known about a request once it has been answered. `request` is
+/// `None` if the request was refused before its head could be read.
+struct Entry<'a> {
+    request: Option<&'a Request>,
+    client: Option<SocketAddr>,
+    response: &'a Response,
+    received: SystemTime,
+    latency: Duration,
+}
+
+impl Entry<'_> {
+    fn common(&self) -> String {
+        let t = DateTime::from_system_time(self.received);
+        let bytes = self.bytes().map_or("-".to_string(), |bytes| bytes.to_string());
+
+        format!(
+            "{} - - [{:02}/{}/{:04}:{:02}:{:02}:{:02} +0000] \"{}\" {} {}\n",
+            self.client(),
+            t.day,
+            t.month_name(),
+            t.year,
+            t.hour,
+            t.minute,
+            t.second,
+            escape(&self.request_line()),
+            self.response.status().code(),
+            bytes,
+        )
+    }
+
+    fn combined(&self) -> String {
+        let mut line = self.common();
+        line.pop();
+        let header = |name| escape(self.header(name).unwrap_or("-"));
+        let _ = writeln!(line, " \"{}\" \"{}\"", header("Referer"), header("User-Agent"));
+        line
+    }
+
+    fn json(&self) -> String {
+        let t = DateTime::from_system_time(self.received);
+        let time = format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z", t.year, t.month, t.day, t.hour, t.minute, t.second);
+        let optional = |value: Option<&str>| value.map_or("null".to_string(), json_string);
+
+        format!(
+            "{{\"time\":{},\"client\":{},\"method\":{},\"target\":{},\"version\":{},\"status\":{},\"bytes\":{},\
+             \"referer\":{},\"user_agent\":{},\"latency_ms\":{:.3},\"request_id\":{}}}\n",
+            json_string(&time),
+            json_string(&self.client()),
+            optional(self.request.map(|request| request.method().as_str())),
+            optional(self.request.map(Request::target)),
+            optional(self.request.map(|request| request.version().as_str())),
+            self.response.status().code(),
+            self.bytes().unwrap_or(0),
+            optional(self.header("Referer")),
+            optional(self.header("User-Agent")),
+            self.latency.as_secs_f64() * 1000.0,
+            optional(self.header("X-Request-Id")),
+        )
+    }
+
+    fn client(&self) -> String {
+        self.client.map_or("-".to_string(), |addr| addr.ip().to_string())
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.request?.header(name)
+    }
+
+    fn request_line(&self) -> String {
+        self.request.map_or("-".to_string(), |request| {
+            format!("{} {} {}", request.method(), request.target(), request.version())
+        })
+    }
+
+    /// The size of the body sent, or `None` if there was none.
+    fn bytes(&self) -> Option<u64> {
+        let len = self.response.body_len();
+        let head = self.request.is_some_and(|request| request.method() == Method::Head);
+        (len > 0 && !head && self.response.status().allows_body()).then_some(len)
+    }
+}
+
+/// Escapes quotes, backslashes and control characters, so that a request
+/// cannot forge log lines or fields.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            c if c.is_control() => escaped.extend(c.escape_default()),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn json_string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(quoted, "\\u{:04x}", u32::from(c));
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http::{Router, StatusCode};
+    use std::time::UNIX_EPOCH;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn request(raw: &str) -> Request {
+        let mut request = Request::read_from(&mut raw.as_bytes()).unwrap();
+        request.set_remote_addr(Some("192.0.2.7:51234".parse().unwrap()));
+        request
+    }
+
+    fn entry<'a>(request: &'a Request, response: &'a Response) -> Entry<'a> {
+        Entry {
+            request: Some(request),
+            client: request.remote_addr(),
+            response,
+            received: UNIX_EPOCH + Duration::from_secs(971_186_136),
+            latency: Duration::from_micros(1500),
+        }
+    }
+
+    #[test]
+    fn formats_common_and_combined_lines() {
+        let request = request(
+            "GET /index.html?q=1 HTTP/1.1\r\nHost: localhost\r\n\
+             Referer: http://example.com/\r\nUser-Agent: curl/8.0 \"test\"\r\n\r\n",
+        );
+        let response = Response::text(StatusCode::Ok, "x".repeat(2326));
+
+        assert_eq!(
+            entry(&request, &response).common(),
+            "192.0.2.7 - - [10/Oct/2000:13:55:36 +0000] \"GET /index.html?q=1 HTTP/1.1\" 200 2326\n"
+        );
+        assert_eq!(
+            entry(&request, &response).combined(),
+            "192.0.2.7 - - [10/Oct/2000:13:55:36 +0000] \"GET /index.html?q=1 HTTP/1.1\" 200 2326 \
+             \"http://example.com/\" \"curl/8.0 \\\"test\\\"\"\n"
+        );
+
+        let bare = Request::read_from(&mut &b"HEAD / HTTP/1.0\r\n\r\n"[..]).unwrap();
+        assert_eq!(
+            entry(&bare, &response).combined(),
+            "- - - [10/Oct/2000:13:55:36 +0000] \"HEAD / HTTP/1.0\" 200 - \"-\" \"-\"\n"
+        );
+    }
+
+    #[test]
+    fn formats_json_lines() {
+        let request =
+            request("POST /a\"b HTTP/1.1\r\nHost: localhost\r\nUser-Agent: tab\there\r\nX-Request-Id: r-1\r\n\r\n");
+        let response = Response::new(StatusCode::NoContent);
+
+        assert_eq!(
+            entry(&request, &response).json(),
+            "{\"time\":\"2000-10-10T13:55:36Z\",\"client\":\"192.0.2.7\",\"method\":\"POST\",\
+             \"target\":\"/a\\\"b\",\"version\":\"HTTP/1.1\",\"status\":204,\"bytes\":0,\"referer\":null,\
+             \"user_agent\":\"tab\\there\",\"latency_ms\":1.500,\"request_id\":\"r-1\"}\n"
+        );
+    }
+
+    #[test]
+    fn formats_refused_requests_without_a_head() {
+        let response = Response::text(StatusCode::BadRequest, "bad");
+        let entry = Entry {
+            request: None,
+            client: Some("192.0.2.7:51234".parse().unwrap()),
+            response: &response,
+            received: UNIX_EPOCH + Duration::from_secs(971_186_136),
+            latency: Duration::ZERO,
+        };
+
+        assert_eq!(entry.combined(), "192.0.2.7 - - [10/Oct/2000:13:55:36 +0000] \"-\" 400 3 \"-\" \"-\"\n");
+        assert!(entry.json().contains("\"method\":null,\"target\":null,\"version\":null,\"status\":400"));
+    }
+
+    #[test]
+    fn escapes_quotes_and_control_characters() {
+        assert_eq!(escape(r#"/a"b\c"#), r#"/a\"b\\c"#);
+        assert_eq!(escape("/a\u{1b}b"), "/a\\u{1b}b");
+        assert_eq!(json_string("a\u{1}\n"), "\"a\\u0001\\n\"");
+    }
+
+    #[test]
+    fn parses_format_names() {
+        assert_eq!("Combined".parse(), Ok(LogFormat::Combined));
+        assert_eq!("json".parse(), Ok(LogFormat::Json));
+        assert!("apache".parse::<LogFormat>().is_err());
+    }
+
+    #[test]
+    fn logs_every_dispatched_request() {
+        let buffer = Buffer::default();
+        let mut router = Router::new();
+        router
+            .get("/", |_: &Request| Response::text(StatusCode::Ok, "hi"))
+            .wrap(AccessLog::new(buffer.clone()).format(LogFormat::Json));
+
+        router.dispatch(&mut request("GET / HTTP/1.1\r\nHost: localhost\r\n\r\n"));
+        router.dispatch(&mut request("GET /missing HTTP/1.0\r\n\r\n"));
+
+        let log = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("\"target\":\"/\",\"version\":\"HTTP/1.1\",\"status\":200,\"bytes\":2"), "{}", lines[0]);
+        assert!(lines[1].contains("\"status\":404"), "{}", lines[1]);
+    }
+}
@@ -0,0 +1,246 @@
+use super::{deflate, Middleware, Next, Request, Response, StatusCode};
+
+/// A [`Middleware`] that compresses responses with gzip or deflate.
+///
+/// A response is compressed when the client accepts gzip or deflate in
+/// `Accept-Encoding`, it is a successful full response of a text-like media
+/// type (`text/*`, JSON, JavaScript, XML or SVG), its body is at least
+/// [`min_size`](Self::min_size) bytes, and compressing actually makes it
+/// smaller. A `304 Not Modified` response for such a type gets the `Vary`
+/// header and `ETag` that the full response would have had.
+#[derive(Debug, Clone)]
+pub struct Compression {
+    min_size: usize,
+}
+
+impl Default for Compression {
+    fn default() -> Self {
+        Compression { min_size: 1024 }
+    }
+}
+
+impl Compression {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Bodies smaller than this are sent as they are. Defaults to 1 KiB.
+    pub fn min_size(mut self, bytes: usize) -> Self {
+        self.min_size = bytes;
+        self
+    }
+
+    /// Compresses `response` if `request` allows it and it is worth it.
+    fn apply(&self, request: &Request, mut response: Response) -> Response {
+        if !is_compressible(&response) {
+            return response;
+        }
+        // Whether or not this one is compressed, caches must key on the header.
+        if !response.headers().has_token("Vary", "Accept-Encoding") {
+            response.headers_mut().append("Vary", "Accept-Encoding");
+        }
+        let encoding = negotiate(request.header("Accept-Encoding").unwrap_or_default());
+
+        // There is no body to compress, but the tag must match the one a full
+        // response to this request would have carried.
+        if response.status() == StatusCode::NotModified {
+            if encoding.is_some() {
+                weaken_etag(&mut response);
+            }
+            return response;
+        }
+
+        // A file body is too large to compress in memory.
+        if response.body().len() < self.min_size || response.file_body().is_some() {
+            return response;
+        }
+        let Some(encoding) = encoding else {
+            return response;
+        };
+
+        let compressed = match encoding {
+            Encoding::Gzip => deflate::gzip(response.body()),
+            Encoding::Deflate => deflate::zlib(response.body()),
+        };
+        if compressed.len() >= response.body().len() {
+            return response;
+        }
+
+        response.headers_mut().set("Content-Encoding", encoding.name());
+        weaken_etag(&mut response);
+        response.with_body(compressed)
+    }
+}
+
+/// The compressed bytes differ from the original, so a strong tag would be
+/// wrong; a weak one still revalidates with `If-None-Match`.
+fn weaken_etag(response: &mut Response) {
+    let headers = response.headers_mut();
+    if let Some(etag) = headers.get("ETag").filter(|etag| !etag.starts_with("W/")) {
+        let weak = format!("W/{etag}");
+        headers.set("ETag", weak);
+    }
+}
+
+impl Middleware for Compression {
+    fn handle(&self, request: &mut Request, next: Next<'_>) -> Response {
+        let response = next.run(request);
+        self.apply(request, response)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Gzip,
+    Deflate,
+}
+
+impl Encoding {
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate",
+        }
+    }
+}
+
+fn is_compressible(response: &Response) -> bool {
+    let headers = response.headers();
+    if !matches!(response.status(), StatusCode::Ok | StatusCode::NotModified)
+        || headers.contains("Content-Encoding")
+        || headers.contains("Content-Range")
+    {
+        return false;
+    }
+
+    let Some(content_type) = headers.get("Content-Type") else {
+        return false;
+    };
+    let media_type = content_type.split(';').next().unwrap_or_default().trim().to_ascii_lowercase();
+
+    media_type.starts_with("text/")
+        || media_type.ends_with("+json")
+        || media_type.ends_with("+xml")
+        || matches!(
+            media_type.as_str(),
+            "application/json" | "application/javascript" | "application/xml" | "image/svg+xml"
+        )
+}
+
+/// Picks the encoding the client prefers from an `Accept-Encoding` value,
+/// favouring gzip on a tie, or `None` to send the body as it is.
+fn negotiate(accept_encoding: &str) -> Option<Encoding> {
+    let mut gzip = None;
+    let mut deflate = None;
+    let mut any = None;
+
+    for item in accept_encoding.split(',') {
+        let mut params = item.split(';');
+        let coding = params.next().unwrap_or_default().trim().to_ascii_lowercase();
+        let quality = params
+            .filter_map(|param| param.trim().strip_prefix("q=").or_else(|| param.trim().strip_prefix("Q=")))
+            .find_map(|q| q.trim().parse::<f32>().ok())
+            .unwrap_or(1.0);
+
+        match coding.as_str() {
+            "gzip" | "x-gzip" => gzip = Some(quality),
+            "deflate" => deflate = Some(quality),
+            "*" => any = Some(quality),
+            _ => {}
+        }
+    }
+
+    let gzip = gzip.or(any).unwrap_or(0.0);
+    let deflate = deflate.or(any).unwrap_or(0.0);
+
+    if gzip > 0.0 && gzip >= deflate {
+        Some(Encoding::Gzip)
+    } else if deflate > 0.0 {
+        Some(Encoding::Deflate)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(accept_encoding: &str) -> Request {
+        let raw = format!("GET / HTTP/1.1\r\nHost: localhost\r\nAccept-Encoding: {accept_encoding}\r\n\r\n");
+        Request::read_from(&mut raw.as_bytes()).unwrap()
+    }
+
+    fn page() -> Response {
+        Response::html(StatusCode::Ok, "<p>hello</p>\n".repeat(200)).header("ETag", "\"abc\"")
+    }
+
+    #[test]
+    fn negotiates_by_quality() {
+        assert_eq!(negotiate("gzip, deflate"), Some(Encoding::Gzip));
+        assert_eq!(negotiate("deflate"), Some(Encoding::Deflate));
+        assert_eq!(negotiate("gzip;q=0.5, deflate;q=0.8"), Some(Encoding::Deflate));
+        assert_eq!(negotiate("br, *;q=0.1"), Some(Encoding::Gzip));
+        assert_eq!(negotiate("gzip;q=0, deflate;q=0"), None);
+        assert_eq!(negotiate("*;q=0, identity"), None);
+        assert_eq!(negotiate("br"), None);
+        assert_eq!(negotiate(""), None);
+    }
+
+    #[test]
+    fn compresses_eligible_responses() {
+        let original = page();
+        let response = Compression::new().apply(&request("gzip"), page());
+
+        assert_eq!(response.headers().get("Content-Encoding"), Some("gzip"));
+        assert_eq!(response.headers().get("Vary"), Some("Accept-Encoding"));
+        assert_eq!(response.headers().get("ETag"), Some("W/\"abc\""));
+        assert_eq!(response.body(), deflate::gzip(original.body()));
+        assert!(response.body().len() < original.body().len());
+
+        let response = Compression::new().apply(&request("deflate"), page());
+        assert_eq!(response.headers().get("Content-Encoding"), Some("deflate"));
+    }
+
+    #[test]
+    fn describes_not_modified_responses_like_full_ones() {
+        let not_modified = || Response::new(StatusCode::NotModified).content_type("text/css").header("ETag", "\"abc\"");
+
+        let response = Compression::new().apply(&request("gzip"), not_modified());
+        assert_eq!(response.headers().get("Vary"), Some("Accept-Encoding"));
+        assert_eq!(response.headers().get("ETag"), Some("W/\"abc\""));
+        assert!(response.body().is_empty());
+
+        let response = Compression::new().apply(&request("identity"), not_modified());
+        assert_eq!(response.headers().get("Vary"), Some("Accept-Encoding"));
+        assert_eq!(response.headers().get("ETag"), Some("\"abc\""));
+
+        let image = Response::new(StatusCode::NotModified).content_type("image/png").header("ETag", "\"abc\"");
+        let response = Compression::new().apply(&request("gzip"), image);
+        assert!(!response.headers().contains("Vary"));
+        assert_eq!(response.headers().get("ETag"), Some("\"abc\""));
+    }
+
+    #[test]
+    fn leaves_other_responses_alone() {
+        let compression = Compression::new();
+
+        let response = compression.apply(&request("identity"), page());
+        assert!(!response.headers().contains("Content-Encoding"));
+        assert_eq!(response.headers().get("Vary"), Some("Accept-Encoding"));
+
+        let small = Response::text(StatusCode::Ok, "tiny");
+        assert!(!compression.apply(&request("gzip"), small).headers().contains("Content-Encoding"));
+
+        let image = Response::new(StatusCode::Ok).content_type("image/png").with_body(vec![0; 4096]);
+        let response = compression.apply(&request("gzip"), image);
+        assert!(!response.headers().contains("Content-Encoding"));
+        assert!(!response.headers().contains("Vary"));
+
+        let partial = page().with_status(StatusCode::PartialContent);
+        assert!(!compression.apply(&request("gzip"), partial).headers().contains("Content-Encoding"));
+
+        let small_enough = Compression::new().min_size(4).apply(&request("gzip"), Response::text(StatusCode::Ok, "tiny"));
+        assert!(!small_enough.headers().contains("Content-Encoding"), "not compressed when it grows");
+    }
+}
@@ -0,0 +1,616 @@
+use std::{
+    io::{self, BufRead, BufReader, BufWriter, Read, Write},
+    net::{SocketAddr, TcpStream},
+    panic::{self, AssertUnwindSafe},
+    time::{Duration, Instant},
+};
+
+use super::{
+    request::{MAX_BODY_SIZE, MAX_HEADERS, MAX_HEAD_SIZE},
+    AccessLog, Method, ParseError, Request, Response, Router, ServerError, StatusCode, Version,
+};
+use crate::{panic_message, signal};
+
+/// Settings for [`serve_connection`].
+///
+/// The timeouts and size limits keep a slow or hostile client from holding a
+/// pool worker for long: a client that sends its request a byte at a time
+/// still has to finish the headers within the header timeout.
+///
+/// ```
+/// use std::time::Duration;
+/// use server::http::ConnectionConfig;
+///
+/// let config = ConnectionConfig::new()
+///     .idle_timeout(Duration::from_secs(2))
+///     .header_timeout(Duration::from_secs(5))
+///     .max_header_size(16 * 1024)
+///     .max_body_size(64 * 1024)
+///     .max_requests(50);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConnectionConfig {
+    idle_timeout: Duration,
+    header_timeout: Duration,
+    body_timeout: Duration,
+    write_timeout: Duration,
+    max_header_size: usize,
+    max_headers: usize,
+    max_body_size: u64,
+    max_requests: usize,
+    shutting_down: fn() -> bool,
+    access_log: Option<AccessLog>,
+}
+
+impl Default for ConnectionConfig {
+    fn default() -> Self {
+        ConnectionConfig {
+            idle_timeout: Duration::from_secs(5),
+            header_timeout: Duration::from_secs(10),
+            body_timeout: Duration::from_secs(30),
+            write_timeout: Duration::from_secs(30),
+            max_header_size: MAX_HEAD_SIZE,
+            max_headers: MAX_HEADERS,
+            max_body_size: MAX_BODY_SIZE,
+            max_requests: 100,
+            shutting_down: signal::shutdown_requested,
+            access_log: None,
+        }
+    }
+}
+
+impl ConnectionConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How long a persistent connection may wait for its next request before
+    /// it is closed. Defaults to 5 seconds.
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = at_least_1ms(timeout);
+        self
+    }
+
+    /// How long a client has to send the request line and headers, counted
+    /// from connecting or from the first byte of a later request. Defaults
+    /// to 10 seconds. A client that runs out gets a 408 response.
+    pub fn header_timeout(mut self, timeout: Duration) -> Self {
+        self.header_timeout = at_least_1ms(timeout);
+        self
+    }
+
+    /// How long a client has to send the body once the headers are in.
+    /// Defaults to 30 seconds. A client that runs out gets a 408 response.
+    pub fn body_timeout(mut self, timeout: Duration) -> Self {
+        self.body_timeout = at_least_1ms(timeout);
+        self
+    }
+
+    /// How long writing to the client may block before the connection is
+    /// given up on. Defaults to 30 seconds.
+    pub fn write_timeout(mut self, timeout: Duration) -> Self {
+        self.write_timeout = at_least_1ms(timeout);
+        self
+    }
+
+    /// The most bytes the request line and headers may take together.
+    /// Defaults to 8 KiB. A longer request line gets a 414 response and
+    /// larger headers a 431 response.
+    pub fn max_header_size(mut self, bytes: usize) -> Self {
+        self.max_header_size = bytes;
+        self
+    }
+
+    /// The most header fields a request may have. Defaults to 100. More get
+    /// a 431 response.
+    pub fn max_headers(mut self, max: usize) -> Self {
+        self.max_headers = max;
+        self
+    }
+
+    /// The most bytes a request body may have. Defaults to 1 MiB. A larger
+    /// body gets a 413 response, as soon as its size is known.
+    pub fn max_body_size(mut self, bytes: u64) -> Self {
+        self.max_body_size = bytes;
+        self
+    }
+
+    /// How many requests one connection may send before it is closed.
+    /// Defaults to 100; values below 1 are treated as 1.
+    pub fn max_requests(mut self, max: usize) -> Self {
+        self.max_requests = max.max(1);
+        self
+    }
+
+    /// Tells whether the server is shutting down, after which connections
+    /// finish the request in hand and are closed instead of kept alive.
+    /// Defaults to [`signal::shutdown_requested`].
+    pub fn shutdown_check(mut self, shutting_down: fn() -> bool) -> Self {
+        self.shutting_down = shutting_down;
+        self
+    }
+
+    /// Logs the requests that are answered without reaching the router,
+    /// because they could not be read or were too large or too slow. Give it
+    /// a clone of the router's [`AccessLog`] to log every request in one
+    /// place. Not logged by default.
+    pub fn access_log(mut self, log: AccessLog) -> Self {
+        self.access_log = Some(log);
+        self
+    }
+}
+
+/// A zero timeout means "no timeout" to `set_read_timeout`.
+fn at_least_1ms(timeout: Duration) -> Duration {
+    timeout.max(Duration::from_millis(1))
+}
+
+/// Answers requests on `stream` with `router` until the client closes the
+/// connection or it should not be kept alive any longer.
+///
+/// HTTP/1.1 connections are persistent unless a request or response says
+/// `Connection: close`; HTTP/1.0 ones only if the request says
+/// `Connection: keep-alive`. Note that a connection occupies its pool worker
+/// for as long as it is kept open. Once the server shuts down, the request
+/// in hand is answered with `Connection: close` and no more are read.
+///
+/// Nothing a client or handler does makes this panic. A request that cannot
+/// be parsed, is too large or is sent too slowly gets a 4xx or 501
+/// response, and a handler that panics or makes a response that cannot be
+/// sent gets the client a 500 response; either way the connection is then
+/// closed.
+pub fn serve_connection(stream: &TcpStream, router: &Router, config: &ConnectionConfig) -> Result<(), ServerError> {
+    let peer = stream.peer_addr().ok();
+    stream.set_write_timeout(Some(config.write_timeout))?;
+    let mut reader = BufReader::new(DeadlineReader::new(stream));
+    let mut writer = BufWriter::new(stream);
+
+    for served in 1..=config.max_requests {
+        if served > 1 && (config.shutting_down)() {
+            break;
+        }
+
+        // A new connection has the header timeout to send its whole first
+        // head, however long it takes to start. Later requests get their
+        // header timeout once their first byte is in.
+        let wait = if served == 1 { config.header_timeout } else { config.idle_timeout };
+        reader.get_mut().expire_in(wait);
+        match reader.fill_buf() {
+            Ok([]) => return Ok(()),
+            Ok(_) => {}
+            // Also how the idle timeout ends up here.
+            Err(e) if is_timeout(&e) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+        let started = Instant::now();
+        if served > 1 {
+            reader.get_mut().expire_in(config.header_timeout);
+        }
+
+        // The head against the deadline set above, then the body against its own.
+        let mut request = match Request::read_head(&mut reader, config.max_header_size, config.max_headers) {
+            Ok(request) => request,
+            Err(e) => return reject(&mut writer, config, Refused { peer, head: None, started }, e),
+        };
+        request.set_remote_addr(peer);
+        // Such a client waits for this before it sends the body.
+        if request.version() == Version::Http11 && request.headers().has_token("Expect", "100-continue") {
+            writer.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+            writer.flush()?;
+        }
+        reader.get_mut().expire_in(config.body_timeout);
+        if let Err(e) = request.read_body(&mut reader, config.max_body_size) {
+            return reject(&mut writer, config, Refused { peer, head: Some(&request), started }, e);
+        }
+
+        // The pool would survive a panic too, but the client would get no answer.
+        let mut response = match panic::catch_unwind(AssertUnwindSafe(|| router.dispatch(&mut request))) {
+            Ok(response) => response,
+            Err(payload) => {
+                internal_error(&mut writer)?;
+                return Err(ServerError::HandlerPanicked(panic_message(&*payload).to_string()));
+            }
+        };
+        let keep_alive =
+            served < config.max_requests && !(config.shutting_down)() && wants_keep_alive(&request, &response);
+
+        if !keep_alive && !response.headers().has_token("Connection", "close") {
+            response = response.header("Connection", "close");
+        } else if keep_alive && request.version() == Version::Http10 {
+            response = response.header("Connection", "keep-alive");
+        }
+
+        match response.write_to(&mut writer, request.method() == Method::Head) {
+            Ok(()) => {}
+            // Nothing has been sent yet when a header is refused.
+            Err(e) if e.kind() == io::ErrorKind::InvalidInput => {
+                internal_error(&mut writer)?;
+                return Err(ServerError::InvalidResponse(e));
+            }
+            Err(e) => return Err(e.into()),
+        }
+        if !keep_alive {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads from a stream until a deadline, after which reads fail with
+/// [`io::ErrorKind::TimedOut`]. Unlike a plain read timeout, this bounds
+/// the total time, however slowly the bytes trickle in.
+struct DeadlineReader<'a> {
+    stream: &'a TcpStream,
+    deadline: Instant,
+}
+
+impl<'a> DeadlineReader<'a> {
+    fn new(stream: &'a TcpStream) -> Self {
+        DeadlineReader { stream, deadline: Instant::now() }
+    }
+
+    fn expire_in(&mut self, timeout: Duration) {
+        self.deadline = Instant::now() + timeout;
+    }
+}
+
+impl Read for DeadlineReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        let mut stream = self.stream;
+        stream.read(buf)
+    }
+}
+
+fn wants_keep_alive(request: &Request, response: &Response) -> bool {
+    if request.headers().has_token("Connection", "close") || response.headers().has_token("Connection", "close") {
+        return false;
+    }
+    match request.version() {
+        Version::Http11 => true,
+        Version::Http10 => request.headers().has_token("Connection", "keep-alive"),
+    }
+}
+
+/// What is known about a request that could not be read.
+struct Refused<'a> {
+    peer: Option<SocketAddr>,
+    /// The head, if only the body failed.
+    head: Option<&'a Request>,
+    started: Instant,
+}
+
+/// Answers a request that could not be read, and logs it. Nothing after it
+/// on the connection can be trusted, so the connection is closed.
+fn reject<W: Write>(
+    writer: &mut W,
+    config: &ConnectionConfig,
+    refused: Refused<'_>,
+    error: ParseError,
+) -> Result<(), ServerError> {
+    let status = match error {
+        ParseError::Closed => return Ok(()),
+        ParseError::Io(e) if !is_timeout(&e) => return Err(e.into()),
+        ParseError::Io(_) => StatusCode::RequestTimeout,
+        ParseError::NotImplemented(_) => StatusCode::NotImplemented,
+        ParseError::Malformed(_) => StatusCode::BadRequest,
+        ParseError::UriTooLong => StatusCode::UriTooLong,
+        ParseError::HeadersTooLarge => StatusCode::RequestHeaderFieldsTooLarge,
+        ParseError::PayloadTooLarge => StatusCode::PayloadTooLarge,
+    };
+
+    let response = Response::text(status, error.to_string()).header("Connection", "close");
+    if let Some(log) = &config.access_log {
+        log.log_refused(refused.peer, refused.head, &response, refused.started);
+    }
+    Ok(response.write_to(writer, false)?)
+}
+
+/// Answers in place of a handler that failed, and closes the connection.
+fn internal_error<W: Write>(writer: &mut W) -> io::Result<()> {
+    Response::error(StatusCode::InternalServerError).header("Connection", "close").write_to(writer, false)
+}
+
+fn is_timeout(e: &io::Error) -> bool {
+    matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        io::Read,
+        net::TcpListener,
+        sync::{
+            atomic::{AtomicBool, Ordering::SeqCst},
+            Arc, Mutex,
+        },
+        thread,
+        time::Instant,
+    };
+
+    /// Stands in for a shutdown signal, which would reach every test. Only
+    /// `/stop` sets it.
+    static STOPPING: AtomicBool = AtomicBool::new(false);
+
+    /// Serves one connection on a background thread and returns everything
+    /// it sent back for `input`, with how serving it ended.
+    fn serve(config: ConnectionConfig, input: &str) -> (String, Result<(), ServerError>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut router = Router::new();
+            router.get("/", |req: &Request| Response::text(StatusCode::Ok, req.query("n").unwrap_or("-")));
+            router.get("/bye", |_: &Request| Response::text(StatusCode::Ok, "bye").header("Connection", "close"));
+            router.get("/panic", |_: &Request| -> Response { panic!("handler bug") });
+            router.get("/stop", |_: &Request| {
+                STOPPING.store(true, SeqCst);
+                Response::text(StatusCode::Ok, "stopping")
+            });
+            router.get("/split", |_: &Request| Response::new(StatusCode::Ok).header("X-Bad", "a\r\nb"));
+            serve_connection(&stream, &router, &config)
+        });
+
+        let mut client = TcpStream::connect(address).unwrap();
+        client.write_all(input.as_bytes()).unwrap();
+        let mut output = String::new();
+        client.read_to_string(&mut output).unwrap();
+
+        (output, server.join().unwrap())
+    }
+
+    fn exchange(config: ConnectionConfig, input: &str) -> String {
+        let (output, result) = serve(config, input);
+        result.unwrap();
+        output
+    }
+
+    #[test]
+    fn serves_several_requests_on_one_connection() {
+        let output = exchange(
+            ConnectionConfig::new(),
+            "GET /?n=1 HTTP/1.1\r\nHost: localhost\r\n\r\n\
+             GET /?n=2 HTTP/1.1\r\nHost: localhost\r\n\r\n\
+             GET /?n=3 HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n\
+             GET /?n=4 HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        );
+
+        assert_eq!(output.matches("HTTP/1.1 200 OK").count(), 3);
+        assert!(output.ends_with("Connection: close\r\nContent-Length: 1\r\n\r\n3"));
+    }
+
+    #[test]
+    fn response_can_close_the_connection() {
+        let output = exchange(
+            ConnectionConfig::new(),
+            "GET /bye HTTP/1.1\r\nHost: localhost\r\n\r\nGET / HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        );
+
+        assert_eq!(output.matches("HTTP/1.1 200 OK").count(), 1);
+    }
+
+    #[test]
+    fn caps_requests_per_connection() {
+        let output =
+            exchange(ConnectionConfig::new().max_requests(2), &"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n".repeat(3));
+
+        assert_eq!(output.matches("HTTP/1.1 200 OK").count(), 2);
+        assert_eq!(output.matches("Connection: close").count(), 1);
+    }
+
+    #[test]
+    fn http_1_0_needs_keep_alive_to_persist() {
+        let output = exchange(ConnectionConfig::new(), &"GET / HTTP/1.0\r\n\r\n".repeat(2));
+        assert_eq!(output.matches("HTTP/1.1 200 OK").count(), 1);
+
+        let output = exchange(
+            ConnectionConfig::new(),
+            "GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\nGET / HTTP/1.0\r\n\r\n",
+        );
+        assert_eq!(output.matches("HTTP/1.1 200 OK").count(), 2);
+        assert_eq!(output.matches("Connection: keep-alive").count(), 1);
+    }
+
+    #[test]
+    fn shutdown_closes_kept_alive_connections() {
+        let config = ConnectionConfig::new().shutdown_check(|| STOPPING.load(SeqCst));
+        let output = exchange(
+            config,
+            "GET /?n=1 HTTP/1.1\r\nHost: localhost\r\n\r\n\
+             GET /stop HTTP/1.1\r\nHost: localhost\r\n\r\n\
+             GET /?n=3 HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        );
+
+        assert_eq!(output.matches("HTTP/1.1 200 OK").count(), 2);
+        assert!(output.ends_with("Connection: close\r\nContent-Length: 8\r\n\r\nstopping"), "{output}");
+    }
+
+    #[test]
+    fn idle_connections_are_closed() {
+        let start = Instant::now();
+        let config = ConnectionConfig::new().idle_timeout(Duration::from_millis(100));
+        let output = exchange(config, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n");
+
+        assert_eq!(output.matches("HTTP/1.1 200 OK").count(), 1);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn malformed_requests_get_400_and_close() {
+        let output = exchange(ConnectionConfig::new(), "nonsense\r\n\r\nGET / HTTP/1.1\r\nHost: localhost\r\n\r\n");
+
+        assert!(output.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert!(!output.contains("200 OK"));
+    }
+
+    #[test]
+    fn failing_handlers_get_500_and_close() {
+        let (output, result) = serve(
+            ConnectionConfig::new(),
+            "GET /panic HTTP/1.1\r\nHost: localhost\r\n\r\nGET / HTTP/1.1\r\nHost: localhost\r\n\r\n",
+        );
+        assert!(output.starts_with("HTTP/1.1 500 Internal Server Error\r\n"), "{output}");
+        assert!(!output.contains("200 OK"));
+        assert!(matches!(result, Err(ServerError::HandlerPanicked(message)) if message == "handler bug"));
+
+        let (output, result) = serve(ConnectionConfig::new(), "GET /split HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert!(output.starts_with("HTTP/1.1 500 Internal Server Error\r\n"), "{output}");
+        assert!(!output.contains("X-Bad"));
+        assert!(matches!(result, Err(ServerError::InvalidResponse(_))));
+    }
+
+    #[test]
+    fn disconnects_are_recognised() {
+        let reset = ServerError::Io(io::ErrorKind::ConnectionReset.into());
+        assert!(reset.is_disconnect());
+        assert!(!ServerError::Io(io::ErrorKind::PermissionDenied.into()).is_disconnect());
+        assert!(!ServerError::HandlerPanicked("boom".to_string()).is_disconnect());
+    }
+
+    #[test]
+    fn slow_clients_get_408() {
+        let config = || ConnectionConfig::new().header_timeout(Duration::from_millis(100)).body_timeout(Duration::from_millis(100));
+
+        let output = exchange(config(), "GET / HTTP/1.1\r\nHost: a\r\n");
+        assert!(output.starts_with("HTTP/1.1 408 Request Timeout\r\n"), "{output}");
+
+        let output = exchange(config(), "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 10\r\n\r\nabc");
+        assert!(output.starts_with("HTTP/1.1 408 Request Timeout\r\n"), "{output}");
+
+        let start = Instant::now();
+        assert_eq!(exchange(config(), ""), "", "a client that never starts a request is just closed");
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn header_timeout_covers_the_whole_first_head() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let config = ConnectionConfig::new().header_timeout(Duration::from_secs(1));
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            serve_connection(&stream, &Router::new(), &config)
+        });
+
+        let mut client = TcpStream::connect(address).unwrap();
+        let mut writer = client.try_clone().unwrap();
+        thread::spawn(move || {
+            // The first byte well into the timeout, then the rest a trickle
+            // that ends past it, though within a timeout of its own.
+            thread::sleep(Duration::from_millis(600));
+            for byte in b"GET / HTTP/1.1\r\nHost: a\r\n\r\n" {
+                if writer.write_all(&[*byte]).is_err() {
+                    break;
+                }
+                thread::sleep(Duration::from_millis(20));
+            }
+        });
+
+        // Had the whole head been read, the empty router would have sent a 404.
+        let mut output = String::new();
+        client.read_to_string(&mut output).unwrap();
+        assert!(output.starts_with("HTTP/1.1 408 Request Timeout\r\n"), "{output}");
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn refused_requests_are_logged() {
+        #[derive(Clone, Default)]
+        struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+        impl Write for Buffer {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                self.0.lock().unwrap().write(buf)
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let buffer = Buffer::default();
+        let config = || ConnectionConfig::new().max_body_size(4).access_log(AccessLog::new(buffer.clone()));
+
+        exchange(config(), "nonsense\r\n\r\n");
+        exchange(config(), "POST /upload HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nabcde");
+
+        let log = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = log.lines().collect();
+        assert_eq!(lines.len(), 2, "{log}");
+        assert!(lines[0].starts_with("127.0.0.1 - - ["), "{}", lines[0]);
+        assert!(lines[0].ends_with("] \"-\" 400 39"), "{}", lines[0]);
+        assert!(lines[1].ends_with("] \"POST /upload HTTP/1.1\" 413 22"), "{}", lines[1]);
+    }
+
+    #[test]
+    fn bodies_are_asked_for_when_expected() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut router = Router::new();
+            router.post("/", |req: &Request| Response::new(StatusCode::Ok).with_body(req.body().to_vec()));
+            serve_connection(&stream, &router, &ConnectionConfig::new())
+        });
+
+        let mut client = TcpStream::connect(address).unwrap();
+        client.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let head = "POST / HTTP/1.1\r\nHost: localhost\r\nExpect: 100-continue\r\nContent-Length: 4\r\nConnection: close\r\n\r\n";
+        client.write_all(head.as_bytes()).unwrap();
+
+        // Nothing of the body has been sent yet.
+        let interim = b"HTTP/1.1 100 Continue\r\n\r\n";
+        let mut buf = vec![0; interim.len()];
+        client.read_exact(&mut buf).unwrap();
+        assert_eq!(buf, interim);
+
+        client.write_all(b"abcd").unwrap();
+        let mut output = String::new();
+        client.read_to_string(&mut output).unwrap();
+        assert!(output.starts_with("HTTP/1.1 200 OK\r\n"), "{output}");
+        assert!(output.ends_with("\r\n\r\nabcd"), "{output}");
+        server.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn oversized_heads_are_refused() {
+        let config = || ConnectionConfig::new().max_header_size(256).max_headers(4);
+
+        let output = exchange(config(), &format!("GET /{} HTTP/1.1\r\nHost: localhost\r\n\r\n", "a".repeat(300)));
+        assert!(output.starts_with("HTTP/1.1 414 URI Too Long\r\n"), "{output}");
+
+        let output = exchange(config(), &format!("GET / HTTP/1.1\r\nHost: localhost\r\nX-Big: {}\r\n\r\n", "b".repeat(300)));
+        assert!(output.starts_with("HTTP/1.1 431 Request Header Fields Too Large\r\n"), "{output}");
+
+        let output = exchange(config(), "GET / HTTP/1.1\r\nHost: localhost\r\nA: 1\r\nB: 2\r\nC: 3\r\nD: 4\r\n\r\n");
+        assert!(output.starts_with("HTTP/1.1 431 Request Header Fields Too Large\r\n"), "{output}");
+
+        let output = exchange(config(), "GET / HTTP/1.1\r\nHost: localhost\r\nA: 1\r\nB: 2\r\nConnection: close\r\n\r\n");
+        assert!(output.starts_with("HTTP/1.1 200 OK\r\n"), "{output}");
+    }
+
+    #[test]
+    fn oversized_bodies_get_413() {
+        let config = || ConnectionConfig::new().max_body_size(4);
+
+        let output = exchange(config(), "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nabcde");
+        assert!(output.starts_with("HTTP/1.1 413 Payload Too Large\r\n"), "{output}");
+
+        let chunked = "POST / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n3\r\nabc\r\n2\r\nde\r\n0\r\n\r\n";
+        let output = exchange(config(), chunked);
+        assert!(output.starts_with("HTTP/1.1 413 Payload Too Large\r\n"), "{output}");
+
+        let output =
+            exchange(config(), "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 4\r\nConnection: close\r\n\r\nabcd");
+        assert!(output.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"), "{output}");
+    }
+}
@@ -0,0 +1,292 @@
+//! DEFLATE (RFC 1951) compression with the zlib (RFC 1950) and gzip
+//! (RFC 1952) wrappers.
+//!
+//! The compressor finds repeats with LZ77 over hash chains and encodes them
+//! with the fixed Huffman codes, in a single block. That gets most of the gain
+//! on the text responses it is used for, without building dynamic tables.
+
+/// Sliding window size of DEFLATE.
+const WINDOW: usize = 32 * 1024;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+/// How many earlier positions with the same hash are tried per match.
+const MAX_CHAIN: usize = 64;
+const HASH_BITS: u32 = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145,
+    8193, 12289, 16385, 24577,
+];
+const DIST_EXTRA: [u8; 30] = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+
+/// Compresses `data` into a raw DEFLATE stream.
+pub(super) fn deflate(data: &[u8]) -> Vec<u8> {
+    let mut out = BitWriter::default();
+    // BFINAL = 1, BTYPE = 01 (fixed Huffman codes).
+    out.write_bits(1, 1);
+    out.write_bits(1, 2);
+
+    let mut head = vec![usize::MAX; 1 << HASH_BITS];
+    let mut prev = vec![usize::MAX; WINDOW];
+    let mut pos = 0;
+
+    while pos < data.len() {
+        match longest_match(data, pos, &head, &prev) {
+            Some((length, distance)) => {
+                write_match(&mut out, length, distance);
+                for p in pos..pos + length {
+                    insert(data, p, &mut head, &mut prev);
+                }
+                pos += length;
+            }
+            None => {
+                write_literal(&mut out, data[pos].into());
+                insert(data, pos, &mut head, &mut prev);
+                pos += 1;
+            }
+        }
+    }
+
+    write_literal(&mut out, 256);
+    out.finish()
+}
+
+/// Compresses `data` into the zlib format used by `Content-Encoding: deflate`.
+pub(super) fn zlib(data: &[u8]) -> Vec<u8> {
+    // CMF: deflate with a 32 KiB window; FLG: no dictionary, check bits.
+    let mut out = vec![0x78, 0x01];
+    out.extend(deflate(data));
+    out.extend(adler32(data).to_be_bytes());
+    out
+}
+
+/// Compresses `data` into the gzip format.
+pub(super) fn gzip(data: &[u8]) -> Vec<u8> {
+    // Magic, deflate, no flags, no modification time, no extra flags, unknown OS.
+    let mut out = vec![0x1f, 0x8b, 8, 0, 0, 0, 0, 0, 0, 0xff];
+    out.extend(deflate(data));
+    out.extend(crc32(data).to_le_bytes());
+    out.extend((data.len() as u32).to_le_bytes());
+    out
+}
+
+pub(super) fn crc32(data: &[u8]) -> u32 {
+    static TABLE: std::sync::OnceLock<[u32; 256]> = std::sync::OnceLock::new();
+    let table = TABLE.get_or_init(|| {
+        let mut table = [0; 256];
+        for (n, entry) in table.iter_mut().enumerate() {
+            *entry = (0..8).fold(n as u32, |c, _| if c & 1 == 1 { 0xedb8_8320 ^ (c >> 1) } else { c >> 1 });
+        }
+        table
+    });
+
+    !data.iter().fold(!0, |crc, &byte| table[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8))
+}
+
+pub(super) fn adler32(data: &[u8]) -> u32 {
+    const MOD: u32 = 65_521;
+    // 5552 bytes is the most that can be summed before `b` could overflow.
+    let (a, b) = data.chunks(5552).fold((1, 0), |(mut a, mut b), chunk| {
+        for &byte in chunk {
+            a += u32::from(byte);
+            b += a;
+        }
+        (a % MOD, b % MOD)
+    });
+    (b << 16) | a
+}
+
+fn hash(data: &[u8], pos: usize) -> usize {
+    let bytes = u32::from(data[pos]) << 16 | u32::from(data[pos + 1]) << 8 | u32::from(data[pos + 2]);
+    (bytes.wrapping_mul(0x9e37_79b1) >> (32 - HASH_BITS)) as usize
+}
+
+fn insert(data: &[u8], pos: usize, head: &mut [usize], prev: &mut [usize]) {
+    if pos + MIN_MATCH <= data.len() {
+        let h = hash(data, pos);
+        prev[pos % WINDOW] = head[h];
+        head[h] = pos;
+    }
+}
+
+/// Returns the length and distance of the longest earlier match for the
+/// bytes at `pos`, if one is at least `MIN_MATCH` long.
+fn longest_match(data: &[u8], pos: usize, head: &[usize], prev: &[usize]) -> Option<(usize, usize)> {
+    if pos + MIN_MATCH > data.len() {
+        return None;
+    }
+
+    let max = (data.len() - pos).min(MAX_MATCH);
+    let mut best: Option<(usize, usize)> = None;
+    let mut candidate = head[hash(data, pos)];
+
+    for _ in 0..MAX_CHAIN {
+        if candidate == usize::MAX || pos - candidate > WINDOW - 1 {
+            break;
+        }
+        let length = data[candidate..].iter().zip(&data[pos..pos + max]).take_while(|(a, b)| a == b).count();
+        if length >= MIN_MATCH && best.is_none_or(|(best, _)| length > best) {
+            best = Some((length, pos - candidate));
+            if length == max {
+                break;
+            }
+        }
+
+        let next = prev[candidate % WINDOW];
+        // Older entries of the ring buffer may have been overwritten.
+        if next == usize::MAX || next >= candidate {
+            break;
+        }
+        candidate = next;
+    }
+
+    best
+}
+
+fn write_literal(out: &mut BitWriter, symbol: u16) {
+    let (code, bits) = match symbol {
+        0..=143 => (0x30 + symbol, 8),
+        144..=255 => (0x190 + symbol - 144, 9),
+        256..=279 => (symbol - 256, 7),
+        _ => (0xc0 + symbol - 280, 8),
+    };
+    out.write_code(code.into(), bits);
+}
+
+fn write_match(out: &mut BitWriter, length: usize, distance: usize) {
+    let index = LENGTH_BASE.iter().rposition(|&base| usize::from(base) <= length).unwrap_or_default();
+    write_literal(out, 257 + index as u16);
+    out.write_bits((length - usize::from(LENGTH_BASE[index])) as u32, LENGTH_EXTRA[index].into());
+
+    let index = DIST_BASE.iter().rposition(|&base| usize::from(base) <= distance).unwrap_or_default();
+    out.write_code(index as u32, 5);
+    out.write_bits((distance - usize::from(DIST_BASE[index])) as u32, DIST_EXTRA[index].into());
+}
+
+/// Packs bits least significant first, as DEFLATE does.
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    buffer: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write_bits(&mut self, value: u32, count: u32) {
+        self.buffer |= u64::from(value) << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Writes a Huffman code, which goes out most significant bit first.
+    fn write_code(&mut self, code: u32, count: u32) {
+        self.write_bits(code.reverse_bits() >> (32 - count), count);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decodes a single fixed-Huffman block, enough to check our own output.
+    fn inflate(data: &[u8]) -> Vec<u8> {
+        let mut bit = 0;
+        let mut read = |count: u32| {
+            let mut value = 0;
+            for i in 0..count {
+                value |= u32::from(data[bit / 8] >> (bit % 8) & 1) << i;
+                bit += 1;
+            }
+            value
+        };
+        let read_code = |read: &mut dyn FnMut(u32) -> u32, count: u32| (0..count).fold(0, |code, _| code << 1 | read(1));
+
+        assert_eq!(read(3), 0b011, "expected a final fixed-Huffman block");
+        let mut out: Vec<u8> = Vec::new();
+        loop {
+            let mut code = read_code(&mut read, 7);
+            let symbol = if code <= 0x17 {
+                code + 256
+            } else {
+                code = code << 1 | read(1);
+                match code {
+                    0x30..=0xbf => code - 0x30,
+                    0xc0..=0xc7 => code - 0xc0 + 280,
+                    _ => (code << 1 | read(1)) - 0x190 + 144,
+                }
+            };
+
+            match symbol {
+                0..=255 => out.push(symbol as u8),
+                256 => return out,
+                _ => {
+                    let index = symbol as usize - 257;
+                    let length = usize::from(LENGTH_BASE[index]) + read(LENGTH_EXTRA[index].into()) as usize;
+                    let index = read_code(&mut read, 5) as usize;
+                    let distance = usize::from(DIST_BASE[index]) + read(DIST_EXTRA[index].into()) as usize;
+                    for _ in 0..length {
+                        out.push(out[out.len() - distance]);
+                    }
+                }
+            }
+        }
+    }
+
+    fn sample() -> Vec<u8> {
+        let mut data = Vec::new();
+        for i in 0..2000 {
+            data.extend(format!("<li class=\"item\">Item number {i} of the list, {}</li>\n", i * 7919 % 1000).bytes());
+        }
+        data.extend((0..=255u8).cycle().take(3000));
+        data
+    }
+
+    #[test]
+    fn checksums_match_known_values() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+        assert_eq!(adler32(b""), 1);
+    }
+
+    #[test]
+    fn deflate_round_trips_and_shrinks_text() {
+        for data in [Vec::new(), b"a".to_vec(), b"abcabcabcabcabcabc".to_vec(), vec![0; 100_000], sample()] {
+            assert_eq!(inflate(&deflate(&data)), data);
+        }
+
+        let data = sample();
+        assert!(deflate(&data).len() < data.len() / 3);
+    }
+
+    #[test]
+    fn wrappers_have_headers_and_trailers() {
+        let data = b"hello hello hello hello";
+
+        let gz = gzip(data);
+        assert_eq!(gz[..3], [0x1f, 0x8b, 8]);
+        assert_eq!(gz[gz.len() - 8..gz.len() - 4], crc32(data).to_le_bytes());
+        assert_eq!(gz[gz.len() - 4..], (data.len() as u32).to_le_bytes());
+        assert_eq!(inflate(&gz[10..gz.len() - 8]), data);
+
+        let z = zlib(data);
+        assert_eq!(u16::from_be_bytes([z[0], z[1]]) % 31, 0);
+        assert_eq!(z[z.len() - 4..], adler32(data).to_be_bytes());
+        assert_eq!(inflate(&z[2..z.len() - 4]), data);
+    }
+}
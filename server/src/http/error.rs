@@ -0,0 +1,60 @@
+use std::{error::Error, fmt, io};
+
+/// Why [`serve_connection`](super::serve_connection) gave up on a
+/// connection.
+///
+/// Requests that cannot be parsed are not errors of the server: they are
+/// answered with a 400 or 501 response and the connection is closed.
+#[derive(Debug)]
+pub enum ServerError {
+    /// Reading from or writing to the connection failed, often because the
+    /// client went away. See [`is_disconnect`](Self::is_disconnect).
+    Io(io::Error),
+    /// A handler panicked with this message. The client got a 500 response.
+    HandlerPanicked(String),
+    /// A handler made a response that cannot be sent, such as one with a
+    /// line break in a header. The client got a 500 response instead.
+    InvalidResponse(io::Error),
+}
+
+impl ServerError {
+    /// Whether the client closed or reset the connection, which is not worth
+    /// reporting.
+    pub fn is_disconnect(&self) -> bool {
+        match self {
+            ServerError::Io(e) => matches!(
+                e.kind(),
+                io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::UnexpectedEof
+            ),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for ServerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServerError::Io(e) => write!(f, "connection I/O failed: {e}"),
+            ServerError::HandlerPanicked(message) => write!(f, "handler panicked: {message}"),
+            ServerError::InvalidResponse(e) => write!(f, "handler returned an invalid response: {e}"),
+        }
+    }
+}
+
+impl Error for ServerError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ServerError::Io(e) | ServerError::InvalidResponse(e) => Some(e),
+            ServerError::HandlerPanicked(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for ServerError {
+    fn from(e: io::Error) -> Self {
+        ServerError::Io(e)
+    }
+}
@@ -0,0 +1,102 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+/// Caps how many connections one client address may have open at once, so
+/// that a single client cannot take every pool worker.
+///
+/// ```
+/// use server::http::ConnectionLimiter;
+///
+/// let limiter = ConnectionLimiter::new(1);
+/// let ip = "192.0.2.1".parse().unwrap();
+///
+/// let permit = limiter.try_acquire(ip).unwrap();
+/// assert!(limiter.try_acquire(ip).is_none());
+/// drop(permit);
+/// assert!(limiter.try_acquire(ip).is_some());
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConnectionLimiter {
+    max_per_ip: usize,
+    open: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl ConnectionLimiter {
+    /// Allows up to `max_per_ip` connections per address; values below 1
+    /// are treated as 1.
+    pub fn new(max_per_ip: usize) -> Self {
+        ConnectionLimiter { max_per_ip: max_per_ip.max(1), open: Arc::default() }
+    }
+
+    /// Counts a new connection from `ip`, or returns `None` if it already has
+    /// as many open as allowed. The connection counts until the permit is
+    /// dropped.
+    pub fn try_acquire(&self, ip: IpAddr) -> Option<ConnectionPermit> {
+        let mut open = self.open.lock().unwrap_or_else(PoisonError::into_inner);
+        let count = open.entry(ip).or_default();
+        if *count >= self.max_per_ip {
+            return None;
+        }
+        *count += 1;
+        Some(ConnectionPermit { ip, open: Arc::clone(&self.open) })
+    }
+
+    /// How many connections `ip` has open.
+    pub fn open_connections(&self, ip: IpAddr) -> usize {
+        self.open.lock().unwrap_or_else(PoisonError::into_inner).get(&ip).copied().unwrap_or_default()
+    }
+}
+
+/// One open connection counted by a [`ConnectionLimiter`].
+#[derive(Debug)]
+pub struct ConnectionPermit {
+    ip: IpAddr,
+    open: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        let mut open = self.open.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(count) = open.get_mut(&self.ip) {
+            *count -= 1;
+            // Forget addresses without connections, so the map does not grow
+            // with every client ever seen.
+            if *count == 0 {
+                open.remove(&self.ip);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_connections_per_address() {
+        let limiter = ConnectionLimiter::new(2);
+        let (a, b): (IpAddr, IpAddr) = ("192.0.2.1".parse().unwrap(), "2001:db8::1".parse().unwrap());
+
+        let first = limiter.try_acquire(a).unwrap();
+        let _second = limiter.try_acquire(a).unwrap();
+        assert!(limiter.try_acquire(a).is_none());
+        let _other = limiter.try_acquire(b).expect("other addresses have their own count");
+        assert_eq!(limiter.open_connections(a), 2);
+
+        drop(first);
+        assert_eq!(limiter.open_connections(a), 1);
+        assert!(limiter.try_acquire(a).is_some());
+    }
+
+    #[test]
+    fn forgets_addresses_without_connections() {
+        let limiter = ConnectionLimiter::new(1);
+        let ip: IpAddr = "192.0.2.1".parse().unwrap();
+
+        drop(limiter.clone().try_acquire(ip));
+        assert!(limiter.open.lock().unwrap().is_empty());
+    }
+}
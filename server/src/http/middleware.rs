@@ -0,0 +1,219 @@
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::atomic::{AtomicU64, Ordering},
+    time::Instant,
+};
+
+use super::{Request, Response, Router, StatusCode};
+
+/// Code that runs around every request a [`Router`] dispatches, added with
+/// [`Router::wrap`].
+///
+/// A middleware gets the request before the route handler does and decides
+/// what happens next: it can change the request, answer it by itself, or
+/// pass it on with [`Next::run`] and then change the response.
+///
+/// ```
+/// use server::http::{Next, Request, Response, Router, StatusCode};
+///
+/// let mut router = Router::new();
+/// router
+///     .get("/", |_: &Request| Response::text(StatusCode::Ok, "hello"))
+///     .wrap(|req: &mut Request, next: Next<'_>| {
+///         if req.header("Authorization").is_none() {
+///             return Response::error(StatusCode::Unauthorized);
+///         }
+///         next.run(req).header("X-Checked", "yes")
+///     });
+///
+/// let mut request = Request::read_from(&mut &b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n"[..]).unwrap();
+/// assert_eq!(router.dispatch(&mut request).status(), StatusCode::Unauthorized);
+/// ```
+pub trait Middleware: Send + Sync {
+    fn handle(&self, request: &mut Request, next: Next<'_>) -> Response;
+}
+
+impl<F> Middleware for F
+where F: Fn(&mut Request, Next<'_>) -> Response + Send + Sync {
+    fn handle(&self, request: &mut Request, next: Next<'_>) -> Response {
+        self(request, next)
+    }
+}
+
+/// The rest of the chain after the running middleware: the middlewares
+/// added after it, then the route handler.
+pub struct Next<'a> {
+    chain: &'a [Box<dyn Middleware>],
+    router: &'a Router,
+}
+
+impl<'a> Next<'a> {
+    pub(super) fn new(chain: &'a [Box<dyn Middleware>], router: &'a Router) -> Self {
+        Next { chain, router }
+    }
+
+    /// Passes `request` on and returns the response it ends up with.
+    pub fn run(self, request: &mut Request) -> Response {
+        match self.chain.split_first() {
+            Some((middleware, rest)) => middleware.handle(request, Next::new(rest, self.router)),
+            None => self.router.run_route(request),
+        }
+    }
+}
+
+/// Gives every request an ID in the `X-Request-Id` header, on both the
+/// request (for the handlers and middlewares that run after it) and the
+/// response.
+///
+/// An ID sent by the client, or a proxy in front of the server, is kept if
+/// it is at most 128 visible ASCII characters.
+#[derive(Debug, Default)]
+pub struct RequestId {
+    next: AtomicU64,
+}
+
+impl RequestId {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Middleware for RequestId {
+    fn handle(&self, request: &mut Request, next: Next<'_>) -> Response {
+        let id = match request.header("X-Request-Id") {
+            Some(id) if is_valid_id(id) => id.to_string(),
+            _ => {
+                let id = format!("{:x}-{:x}", std::process::id(), self.next.fetch_add(1, Ordering::Relaxed));
+                request.headers_mut().set("X-Request-Id", id.clone());
+                id
+            }
+        };
+
+        let mut response = next.run(request);
+        response.headers_mut().set("X-Request-Id", id);
+        response
+    }
+}
+
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty() && id.len() <= 128 && id.bytes().all(|b| b.is_ascii_graphic())
+}
+
+/// Reports how long the request took to handle in a `Server-Timing`
+/// header, like `app;dur=1.250` for 1.25 milliseconds.
+///
+/// The time covers the middlewares added after this one and the handler.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timing;
+
+impl Middleware for Timing {
+    fn handle(&self, request: &mut Request, next: Next<'_>) -> Response {
+        let start = Instant::now();
+        let response = next.run(request);
+        let millis = start.elapsed().as_secs_f64() * 1000.0;
+        response.header("Server-Timing", format!("app;dur={millis:.3}"))
+    }
+}
+
+/// Turns a panic in a handler, or a middleware added after this one, into
+/// a 500 response, so the client gets an answer and the connection stays
+/// usable.
+///
+/// The panic is still reported by the panic hook, on standard error by
+/// default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CatchPanic;
+
+impl Middleware for CatchPanic {
+    fn handle(&self, request: &mut Request, next: Next<'_>) -> Response {
+        panic::catch_unwind(AssertUnwindSafe(|| next.run(request)))
+            .unwrap_or_else(|_| Response::error(StatusCode::InternalServerError))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(raw: &str) -> Request {
+        Request::read_from(&mut raw.as_bytes()).unwrap()
+    }
+
+    fn get(router: &Router, path: &str) -> Response {
+        router.dispatch(&mut request(&format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n")))
+    }
+
+    #[test]
+    fn middlewares_run_in_the_order_they_were_added() {
+        let tag = |name: &'static str| {
+            move |req: &mut Request, next: Next<'_>| {
+                let seen = req.header("X-Seen").unwrap_or_default().to_string();
+                req.headers_mut().set("X-Seen", format!("{seen}{name}"));
+                next.run(req).header("X-Order", name)
+            }
+        };
+
+        let mut router = Router::new();
+        router
+            .get("/", |req: &Request| Response::text(StatusCode::Ok, req.header("X-Seen").unwrap_or_default()))
+            .wrap(tag("a"))
+            .wrap(tag("b"));
+
+        let response = get(&router, "/");
+        assert_eq!(response.body(), b"ab");
+        let order: Vec<&str> = response.headers().get_all("X-Order").collect();
+        assert_eq!(order, ["b", "a"]);
+        assert_eq!(get(&router, "/missing").headers().get_all("X-Order").count(), 2, "404s go through the chain too");
+    }
+
+    #[test]
+    fn a_middleware_can_answer_by_itself() {
+        let mut router = Router::new();
+        router
+            .get("/", |_: &Request| -> Response { unreachable!() })
+            .wrap(|_: &mut Request, _: Next<'_>| Response::error(StatusCode::ServiceUnavailable));
+
+        assert_eq!(get(&router, "/").status(), StatusCode::ServiceUnavailable);
+    }
+
+    #[test]
+    fn request_ids_are_generated_or_kept() {
+        let mut router = Router::new();
+        router
+            .get("/", |req: &Request| Response::text(StatusCode::Ok, req.header("X-Request-Id").unwrap_or_default()))
+            .wrap(RequestId::new());
+
+        let first = get(&router, "/");
+        let second = get(&router, "/");
+        let id = first.headers().get("X-Request-Id").unwrap();
+        assert_eq!(first.body(), id.as_bytes());
+        assert_ne!(second.headers().get("X-Request-Id"), Some(id));
+
+        let kept = router.dispatch(&mut request("GET / HTTP/1.1\r\nHost: localhost\r\nX-Request-Id: abc-123\r\n\r\n"));
+        assert_eq!(kept.headers().get("X-Request-Id"), Some("abc-123"));
+        let replaced = router.dispatch(&mut request("GET / HTTP/1.1\r\nHost: localhost\r\nX-Request-Id: a b\r\n\r\n"));
+        assert_ne!(replaced.headers().get("X-Request-Id"), Some("a b"));
+    }
+
+    #[test]
+    fn timing_adds_a_server_timing_header() {
+        let mut router = Router::new();
+        router.get("/", |_: &Request| Response::text(StatusCode::Ok, "hi")).wrap(Timing);
+
+        let response = get(&router, "/");
+        let timing = response.headers().get("Server-Timing").unwrap();
+        assert!(timing.strip_prefix("app;dur=").unwrap().parse::<f64>().is_ok(), "{timing}");
+    }
+
+    #[test]
+    fn panics_become_internal_server_errors() {
+        let mut router = Router::new();
+        router
+            .get("/", |_: &Request| Response::text(StatusCode::Ok, "fine"))
+            .get("/boom", |_: &Request| -> Response { panic!("handler failed") })
+            .wrap(CatchPanic);
+
+        assert_eq!(get(&router, "/boom").status(), StatusCode::InternalServerError);
+        assert_eq!(get(&router, "/").body(), b"fine");
+    }
+}
@@ -0,0 +1,146 @@
+use std::ops::Range;
+
+/// More separate ranges than this in one request are ignored rather than
+/// served, as each costs a part header. Repeated bytes are prevented by
+/// merging ranges instead.
+const MAX_RANGES: usize = 16;
+
+/// What a `Range` header asks of a representation.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) enum ByteRanges {
+    /// The header is malformed, uses a unit other than bytes, or asks for
+    /// too many ranges. The whole representation should be sent instead.
+    Ignored,
+    /// No range overlaps the representation: a 416 response.
+    Unsatisfiable,
+    /// The ranges to send, clamped to the representation and in ascending
+    /// order. Ranges that overlap or touch are merged, so no byte is sent
+    /// twice.
+    Satisfiable(Vec<Range<u64>>),
+}
+
+/// Interprets a `Range` header such as `bytes=0-99, -500` for a
+/// representation of `len` bytes.
+pub(super) fn parse_ranges(header: &str, len: u64) -> ByteRanges {
+    let Some(specs) = header.trim().strip_prefix("bytes=") else {
+        return ByteRanges::Ignored;
+    };
+
+    let mut ranges = Vec::new();
+    let mut parsed = false;
+    for spec in specs.split(',').map(str::trim) {
+        if spec.is_empty() {
+            continue;
+        }
+        let Some(range) = parse_spec(spec) else {
+            return ByteRanges::Ignored;
+        };
+        parsed = true;
+        if let Some(range) = resolve(range, len) {
+            ranges.push(range);
+        }
+    }
+
+    // A header without a single range asks for nothing that could be refused.
+    if !parsed {
+        return ByteRanges::Ignored;
+    }
+    let ranges = merge(ranges);
+    if ranges.len() > MAX_RANGES {
+        ByteRanges::Ignored
+    } else if ranges.is_empty() {
+        ByteRanges::Unsatisfiable
+    } else {
+        ByteRanges::Satisfiable(ranges)
+    }
+}
+
+enum Spec {
+    /// `first-` or `first-last`.
+    From { first: u64, last: Option<u64> },
+    /// `-count`: the last `count` bytes.
+    Suffix(u64),
+}
+
+fn parse_spec(spec: &str) -> Option<Spec> {
+    let (first, last) = spec.split_once('-')?;
+    let number = |s: &str| s.bytes().all(|b| b.is_ascii_digit()).then(|| s.parse::<u64>().ok()).flatten();
+
+    match (first, last) {
+        ("", count) => Some(Spec::Suffix(number(count)?)),
+        (first, "") => Some(Spec::From { first: number(first)?, last: None }),
+        (first, last) => {
+            let (first, last) = (number(first)?, number(last)?);
+            (first <= last).then_some(Spec::From { first, last: Some(last) })
+        }
+    }
+}
+
+fn resolve(spec: Spec, len: u64) -> Option<Range<u64>> {
+    let range = match spec {
+        Spec::From { first, last } => first..last.map_or(len, |last| last.saturating_add(1).min(len)),
+        Spec::Suffix(count) => len.saturating_sub(count)..len,
+    };
+    (range.start < range.end).then_some(range)
+}
+
+/// Sorts `ranges` and merges those that overlap or are adjacent.
+fn merge(mut ranges: Vec<Range<u64>>) -> Vec<Range<u64>> {
+    ranges.sort_by_key(|range| range.start);
+
+    let mut merged: Vec<Range<u64>> = Vec::with_capacity(ranges.len());
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn single(range: Range<u64>) -> ByteRanges {
+        ByteRanges::Satisfiable(vec![range])
+    }
+
+    #[test]
+    fn resolves_every_form_of_range() {
+        assert_eq!(parse_ranges("bytes=0-499", 1000), single(0..500));
+        assert_eq!(parse_ranges("bytes=900-", 1000), single(900..1000));
+        assert_eq!(parse_ranges("bytes=-100", 1000), single(900..1000));
+        assert_eq!(parse_ranges("bytes=990-2000", 1000), single(990..1000));
+        assert_eq!(parse_ranges("bytes=-5000", 1000), single(0..1000));
+        assert_eq!(parse_ranges("bytes=0-0, -1", 1000), ByteRanges::Satisfiable(vec![0..1, 999..1000]));
+    }
+
+    #[test]
+    fn ranges_past_the_end_are_unsatisfiable() {
+        assert_eq!(parse_ranges("bytes=1000-", 1000), ByteRanges::Unsatisfiable);
+        assert_eq!(parse_ranges("bytes=-0", 1000), ByteRanges::Unsatisfiable);
+        assert_eq!(parse_ranges("bytes=0-", 0), ByteRanges::Unsatisfiable);
+        assert_eq!(parse_ranges("bytes=5000-6000, 2000-", 1000), ByteRanges::Unsatisfiable);
+    }
+
+    #[test]
+    fn bad_headers_are_ignored() {
+        assert_eq!(parse_ranges("items=0-1", 1000), ByteRanges::Ignored);
+        assert_eq!(parse_ranges("bytes=5-1", 1000), ByteRanges::Ignored);
+        assert_eq!(parse_ranges("bytes=a-b", 1000), ByteRanges::Ignored);
+        assert_eq!(parse_ranges("bytes=+1-2", 1000), ByteRanges::Ignored);
+        assert_eq!(parse_ranges("bytes=", 1000), ByteRanges::Ignored);
+        assert_eq!(parse_ranges("bytes= , ", 1000), ByteRanges::Ignored);
+        let many: Vec<String> = (0..17).map(|n| format!("{}-{}", n * 2, n * 2)).collect();
+        assert_eq!(parse_ranges(&format!("bytes={}", many.join(",")), 1000), ByteRanges::Ignored);
+    }
+
+    #[test]
+    fn overlapping_ranges_are_merged() {
+        assert_eq!(parse_ranges(&format!("bytes={}", vec!["0-"; 16].join(",")), 1000), single(0..1000));
+        assert_eq!(parse_ranges(&format!("bytes={}", vec!["0-0"; 17].join(",")), 1000), single(0..1));
+        assert_eq!(parse_ranges("bytes=500-599, 0-99, 100-199, 550-", 1000), ByteRanges::Satisfiable(vec![0..200, 500..1000]));
+        assert_eq!(parse_ranges("bytes=-1, 0-0", 1000), ByteRanges::Satisfiable(vec![0..1, 999..1000]));
+    }
+}
@@ -0,0 +1,768 @@
+use std::{
+    error::Error,
+    fmt,
+    io::{self, BufRead, Read},
+    net::SocketAddr,
+    str::FromStr,
+};
+
+use super::{Headers, Response, StatusCode};
+use crate::json::{FromJson, JsonError, Value};
+
+/// Default limit on the size of the request line and headers together.
+pub(super) const MAX_HEAD_SIZE: usize = 8 * 1024;
+/// Default limit on the number of header fields.
+pub(super) const MAX_HEADERS: usize = 100;
+/// Default limit on the size of a request body.
+pub(super) const MAX_BODY_SIZE: u64 = 1024 * 1024;
+/// Limit on a chunk size line of a chunked body, extensions included.
+const MAX_CHUNK_LINE: u64 = 1024;
+
+/// Request method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+    Patch,
+    Options,
+    Trace,
+    Connect,
+}
+
+impl Method {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Method::Get => "GET",
+            Method::Head => "HEAD",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+            Method::Patch => "PATCH",
+            Method::Options => "OPTIONS",
+            Method::Trace => "TRACE",
+            Method::Connect => "CONNECT",
+        }
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+impl FromStr for Method {
+    type Err = ParseError;
+
+    /// Parses a method name. Method names are case-sensitive.
+    fn from_str(s: &str) -> Result<Self, ParseError> {
+        Ok(match s {
+            "GET" => Method::Get,
+            "HEAD" => Method::Head,
+            "POST" => Method::Post,
+            "PUT" => Method::Put,
+            "DELETE" => Method::Delete,
+            "PATCH" => Method::Patch,
+            "OPTIONS" => Method::Options,
+            "TRACE" => Method::Trace,
+            "CONNECT" => Method::Connect,
+            _ if !s.is_empty() && s.bytes().all(is_token_byte) => {
+                return Err(ParseError::NotImplemented("unknown method"));
+            }
+            _ => return Err(ParseError::Malformed("invalid method")),
+        })
+    }
+}
+
+/// HTTP version of a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Version {
+    Http10,
+    Http11,
+}
+
+impl Version {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Version::Http10 => "HTTP/1.0",
+            Version::Http11 => "HTTP/1.1",
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.as_str())
+    }
+}
+
+/// Error returned when a request cannot be read.
+#[derive(Debug)]
+pub enum ParseError {
+    /// The connection was closed before a request started.
+    Closed,
+    /// Reading from the connection failed.
+    Io(io::Error),
+    /// The request is not valid HTTP. The client should get a 400 response.
+    Malformed(&'static str),
+    /// The request is valid but uses a feature we do not support. The client
+    /// should get a 501 response.
+    NotImplemented(&'static str),
+    /// The request line is longer than allowed. The client should get a 414
+    /// response.
+    UriTooLong,
+    /// The header section is larger than allowed or has too many fields. The
+    /// client should get a 431 response.
+    HeadersTooLarge,
+    /// The body is larger than allowed. The client should get a 413
+    /// response.
+    PayloadTooLarge,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Closed => write!(f, "connection closed before a request was sent"),
+            ParseError::Io(e) => write!(f, "failed to read request: {e}"),
+            ParseError::Malformed(reason) => write!(f, "malformed request: {reason}"),
+            ParseError::NotImplemented(reason) => write!(f, "unsupported request: {reason}"),
+            ParseError::UriTooLong => write!(f, "request line too long"),
+            ParseError::HeadersTooLarge => write!(f, "header section too large"),
+            ParseError::PayloadTooLarge => write!(f, "request body too large"),
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ParseError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(e: io::Error) -> Self {
+        ParseError::Io(e)
+    }
+}
+
+/// Why [`Request::json`] could not extract a body.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonRejection {
+    /// The `Content-Type` is missing or not JSON: a 415 response.
+    UnsupportedMediaType,
+    /// The body is not valid JSON or does not have the expected shape: a
+    /// 422 response.
+    Invalid(JsonError),
+}
+
+impl fmt::Display for JsonRejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JsonRejection::UnsupportedMediaType => write!(f, "expected a JSON body (Content-Type: application/json)"),
+            JsonRejection::Invalid(e) => write!(f, "invalid JSON body: {e}"),
+        }
+    }
+}
+
+impl Error for JsonRejection {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            JsonRejection::Invalid(e) => Some(e),
+            JsonRejection::UnsupportedMediaType => None,
+        }
+    }
+}
+
+/// A JSON error response such as `{"error":"invalid JSON body: ..."}`.
+impl From<JsonRejection> for Response {
+    fn from(rejection: JsonRejection) -> Self {
+        let status = match rejection {
+            JsonRejection::UnsupportedMediaType => StatusCode::UnsupportedMediaType,
+            JsonRejection::Invalid(_) => StatusCode::UnprocessableEntity,
+        };
+        Response::json(status, &Value::Object(vec![("error".to_string(), Value::String(rejection.to_string()))]))
+    }
+}
+
+/// A parsed HTTP/1.x request.
+///
+/// ```
+/// use server::http::{Method, Request};
+///
+/// let raw = b"POST /echo?lang=en HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n\r\nhello";
+/// let request = Request::read_from(&mut &raw[..]).unwrap();
+///
+/// assert_eq!(request.method(), Method::Post);
+/// assert_eq!(request.path(), "/echo");
+/// assert_eq!(request.query("lang"), Some("en"));
+/// assert_eq!(request.header("host"), Some("localhost"));
+/// assert_eq!(request.body(), b"hello");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Request {
+    method: Method,
+    target: String,
+    path: String,
+    query: Vec<(String, String)>,
+    version: Version,
+    headers: Headers,
+    body: Vec<u8>,
+    params: Vec<(String, String)>,
+    remote_addr: Option<SocketAddr>,
+}
+
+impl Request {
+    /// Reads one request, including its body, from `reader`.
+    ///
+    /// The request line and headers may take up to 8 KiB together, with at
+    /// most 100 header fields, and the body up to 1 MiB.
+    ///
+    /// Returns [`ParseError::Closed`] if the reader is at its end before the
+    /// request line starts.
+    pub fn read_from<R: BufRead>(reader: &mut R) -> Result<Request, ParseError> {
+        let mut request = Request::read_head(reader, MAX_HEAD_SIZE, MAX_HEADERS)?;
+        request.read_body(reader, MAX_BODY_SIZE)?;
+        Ok(request)
+    }
+
+    /// Reads the request line and headers, which may take up to `max_size`
+    /// bytes together and have at most `max_headers` fields. The body is left
+    /// in `reader` for [`read_body`](Self::read_body).
+    pub(super) fn read_head<R: BufRead>(reader: &mut R, max_size: usize, max_headers: usize) -> Result<Request, ParseError> {
+        let mut line = Vec::new();
+        let mut budget = max_size as u64;
+
+        // A client may send empty lines between requests.
+        loop {
+            match read_line(reader, &mut line, &mut budget) {
+                Ok(true) if line.is_empty() => {}
+                Ok(true) => break,
+                Ok(false) => return Err(ParseError::Closed),
+                Err(ParseError::HeadersTooLarge) => return Err(ParseError::UriTooLong),
+                Err(e) => return Err(e),
+            }
+        }
+
+        let (method, target, version) = parse_request_line(&line)?;
+        let (path, query) = parse_target(method, &target)?;
+        let headers = read_headers(reader, &mut line, &mut budget, max_headers)?;
+        if version == Version::Http11 && !headers.contains("Host") {
+            return Err(ParseError::Malformed("missing Host header"));
+        }
+
+        Ok(Request {
+            method,
+            target,
+            path,
+            query,
+            version,
+            headers,
+            body: Vec::new(),
+            params: Vec::new(),
+            remote_addr: None,
+        })
+    }
+
+    /// Reads the body announced by the headers that [`read_head`](Self::read_head) read,
+    /// which may be up to `max_size` bytes long.
+    pub(super) fn read_body<R: BufRead>(&mut self, reader: &mut R, max_size: u64) -> Result<(), ParseError> {
+        self.body = read_body(reader, &self.headers, max_size)?;
+        Ok(())
+    }
+
+    pub fn method(&self) -> Method {
+        self.method
+    }
+
+    /// The request target as sent, including the query string.
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
+    /// The percent-decoded path of the request target.
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the decoded value of the first query parameter called `name`.
+    pub fn query(&self, name: &str) -> Option<&str> {
+        self.query.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    /// Every decoded query parameter, in order.
+    pub fn query_pairs(&self) -> &[(String, String)] {
+        &self.query
+    }
+
+    pub fn version(&self) -> Version {
+        self.version
+    }
+
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    /// For a [`Middleware`](super::Middleware) to add or change headers
+    /// before the handler sees them.
+    pub fn headers_mut(&mut self) -> &mut Headers {
+        &mut self.headers
+    }
+
+    /// Returns the value of the first header called `name`, ignoring case.
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)
+    }
+
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    /// Parses the body as JSON into a `T`, if `Content-Type` says it is JSON
+    /// (`application/json` or a `+json` type).
+    ///
+    /// The error converts into the 415 or 422 response to send back:
+    ///
+    /// ```
+    /// use server::http::{Request, Response, StatusCode};
+    /// use server::json::Value;
+    ///
+    /// fn create(req: &Request) -> Response {
+    ///     match req.json::<Value>() {
+    ///         Ok(value) => Response::json(StatusCode::Created, &value),
+    ///         Err(rejection) => rejection.into(),
+    ///     }
+    /// }
+    ///
+    /// let raw = b"POST /items HTTP/1.1\r\nHost: localhost\r\nContent-Type: text/plain\r\nContent-Length: 2\r\n\r\n{}";
+    /// let request = Request::read_from(&mut &raw[..]).unwrap();
+    /// assert_eq!(create(&request).status(), StatusCode::UnsupportedMediaType);
+    /// ```
+    pub fn json<T: FromJson>(&self) -> Result<T, JsonRejection> {
+        let media_type = self.header("Content-Type").and_then(|value| value.split(';').next()).unwrap_or_default();
+        let media_type = media_type.trim().to_ascii_lowercase();
+        if media_type != "application/json" && !media_type.ends_with("+json") {
+            return Err(JsonRejection::UnsupportedMediaType);
+        }
+
+        let text = str::from_utf8(&self.body).map_err(|_| JsonRejection::Invalid(JsonError::new("body is not UTF-8")))?;
+        let value = Value::parse(text).map_err(JsonRejection::Invalid)?;
+        T::from_json(&value).map_err(JsonRejection::Invalid)
+    }
+
+    /// Returns the path parameter called `name` captured by the
+    /// [`Router`](super::Router) pattern that matched this request.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    /// Every captured path parameter, in pattern order.
+    pub fn params(&self) -> &[(String, String)] {
+        &self.params
+    }
+
+    /// The address of the client, for a request read by
+    /// [`serve_connection`](super::serve_connection).
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        self.remote_addr
+    }
+
+    pub(super) fn set_remote_addr(&mut self, addr: Option<SocketAddr>) {
+        self.remote_addr = addr;
+    }
+
+    pub(super) fn set_params(&mut self, params: Vec<(String, String)>) {
+        self.params = params;
+    }
+}
+
+/// Reads a line into `line` without its line ending, taking its length out of
+/// `budget`. Returns `false` at the end of input, and
+/// [`ParseError::HeadersTooLarge`] if the line does not end within `budget`.
+fn read_line<R: BufRead>(reader: &mut R, line: &mut Vec<u8>, budget: &mut u64) -> Result<bool, ParseError> {
+    line.clear();
+    let read = reader.take(*budget).read_until(b'\n', line)?;
+    *budget -= read as u64;
+    if read == 0 && *budget > 0 {
+        return Ok(false);
+    }
+    if line.pop() != Some(b'\n') {
+        return Err(if *budget == 0 { ParseError::HeadersTooLarge } else { ParseError::Malformed("incomplete request") });
+    }
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    Ok(true)
+}
+
+fn parse_request_line(line: &[u8]) -> Result<(Method, String, Version), ParseError> {
+    let line = str::from_utf8(line).map_err(|_| ParseError::Malformed("request line is not UTF-8"))?;
+
+    let mut parts = line.split(' ');
+    let (Some(method), Some(target), Some(version), None) = (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return Err(ParseError::Malformed("invalid request line"));
+    };
+
+    let version = match version {
+        "HTTP/1.1" => Version::Http11,
+        "HTTP/1.0" => Version::Http10,
+        _ if version.starts_with("HTTP/") => return Err(ParseError::NotImplemented("unsupported HTTP version")),
+        _ => return Err(ParseError::Malformed("invalid HTTP version")),
+    };
+
+    Ok((method.parse()?, target.to_string(), version))
+}
+
+fn parse_target(method: Method, target: &str) -> Result<(String, Vec<(String, String)>), ParseError> {
+    if target == "*" && method == Method::Options {
+        return Ok((target.to_string(), Vec::new()));
+    }
+    if !target.starts_with('/') {
+        return Err(ParseError::Malformed("request target must be an absolute path"));
+    }
+
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let path = percent_decode(path, false).ok_or(ParseError::Malformed("invalid percent-encoding in path"))?;
+
+    let query = query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Some((percent_decode(key, true)?, percent_decode(value, true)?))
+        })
+        .collect::<Option<_>>()
+        .ok_or(ParseError::Malformed("invalid percent-encoding in query"))?;
+
+    Ok((path, query))
+}
+
+/// Decodes `%XX` escapes, and `+` as a space if `plus_as_space`. Returns
+/// `None` for a bad escape or if the result is not UTF-8.
+fn percent_decode(s: &str, plus_as_space: bool) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut input = s.bytes();
+
+    while let Some(byte) = input.next() {
+        bytes.push(match byte {
+            b'%' => {
+                let high = char::from(input.next()?).to_digit(16)?;
+                let low = char::from(input.next()?).to_digit(16)?;
+                (high * 16 + low) as u8
+            }
+            b'+' if plus_as_space => b' ',
+            _ => byte,
+        });
+    }
+
+    String::from_utf8(bytes).ok()
+}
+
+fn read_headers<R: BufRead>(
+    reader: &mut R,
+    line: &mut Vec<u8>,
+    budget: &mut u64,
+    max_headers: usize,
+) -> Result<Headers, ParseError> {
+    let mut headers = Headers::new();
+
+    loop {
+        if !read_line(reader, line, budget)? {
+            return Err(ParseError::Malformed("incomplete request"));
+        }
+        if line.is_empty() {
+            return Ok(headers);
+        }
+        if headers.len() == max_headers {
+            return Err(ParseError::HeadersTooLarge);
+        }
+        let (name, value) = parse_header(line)?;
+        headers.append(name, value);
+    }
+}
+
+fn parse_header(line: &[u8]) -> Result<(&str, &str), ParseError> {
+    let line = str::from_utf8(line).map_err(|_| ParseError::Malformed("header is not UTF-8"))?;
+    let (name, value) = line.split_once(':').ok_or(ParseError::Malformed("header without a colon"))?;
+
+    // This also rejects obsolete line folding, where a line starts with whitespace.
+    if name.is_empty() || !name.bytes().all(is_token_byte) {
+        return Err(ParseError::Malformed("invalid header name"));
+    }
+
+    Ok((name, value.trim_matches([' ', '\t'])))
+}
+
+fn read_body<R: BufRead>(reader: &mut R, headers: &Headers, max_size: u64) -> Result<Vec<u8>, ParseError> {
+    if let Some(encoding) = headers.get("Transfer-Encoding") {
+        if headers.contains("Content-Length") {
+            return Err(ParseError::Malformed("both Transfer-Encoding and Content-Length"));
+        }
+        if headers.get_all("Transfer-Encoding").count() > 1 || !encoding.trim().eq_ignore_ascii_case("chunked") {
+            return Err(ParseError::NotImplemented("unsupported transfer encoding"));
+        }
+        return read_chunked(reader, max_size);
+    }
+
+    let mut lengths = headers.get_all("Content-Length").map(parse_content_length);
+    let Some(length) = lengths.next().transpose()? else {
+        return Ok(Vec::new());
+    };
+    if lengths.any(|other| other.ok() != Some(length)) {
+        return Err(ParseError::Malformed("conflicting Content-Length headers"));
+    }
+    // Refused before reading any of it, so the client can be told early.
+    if length > max_size {
+        return Err(ParseError::PayloadTooLarge);
+    }
+
+    let mut body = Vec::new();
+    read_exact(reader, length, &mut body)?;
+    Ok(body)
+}
+
+fn parse_content_length(value: &str) -> Result<u64, ParseError> {
+    let value = value.trim();
+    if value.is_empty() || !value.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(ParseError::Malformed("invalid Content-Length"));
+    }
+    value.parse().map_err(|_| ParseError::Malformed("invalid Content-Length"))
+}
+
+fn read_chunked<R: BufRead>(reader: &mut R, max_size: u64) -> Result<Vec<u8>, ParseError> {
+    let mut line = Vec::new();
+    let mut body = Vec::new();
+
+    loop {
+        let mut budget = MAX_CHUNK_LINE;
+        let more = read_line(reader, &mut line, &mut budget).map_err(|e| match e {
+            ParseError::HeadersTooLarge => ParseError::Malformed("chunk size line too long"),
+            e => e,
+        })?;
+        if !more {
+            return Err(ParseError::Malformed("incomplete chunked body"));
+        }
+        let size = parse_chunk_size(&line)?;
+        if size == 0 {
+            break;
+        }
+        if size > max_size - body.len() as u64 {
+            return Err(ParseError::PayloadTooLarge);
+        }
+
+        read_exact(reader, size, &mut body)?;
+        if !read_line(reader, &mut line, &mut budget)? || !line.is_empty() {
+            return Err(ParseError::Malformed("chunk not followed by a line break"));
+        }
+    }
+
+    // Trailer fields are read but not kept.
+    let mut budget = MAX_HEAD_SIZE as u64;
+    loop {
+        if !read_line(reader, &mut line, &mut budget)? {
+            return Err(ParseError::Malformed("incomplete chunked body"));
+        }
+        if line.is_empty() {
+            return Ok(body);
+        }
+        parse_header(&line)?;
+    }
+}
+
+fn parse_chunk_size(line: &[u8]) -> Result<u64, ParseError> {
+    // Chunk extensions after `;` are ignored.
+    let size = line.split(|&b| b == b';').next().unwrap_or_default().trim_ascii();
+
+    if size.is_empty() || !size.iter().all(u8::is_ascii_hexdigit) {
+        return Err(ParseError::Malformed("invalid chunk size"));
+    }
+    str::from_utf8(size)
+        .ok()
+        .and_then(|size| u64::from_str_radix(size, 16).ok())
+        .ok_or(ParseError::Malformed("invalid chunk size"))
+}
+
+/// Appends exactly `length` bytes from `reader` to `body`.
+fn read_exact<R: BufRead>(reader: &mut R, length: u64, body: &mut Vec<u8>) -> Result<(), ParseError> {
+    // `take` rather than a buffer of `length` bytes, so a bogus length does
+    // not allocate more than the client actually sends.
+    let read = reader.take(length).read_to_end(body)?;
+    if (read as u64) < length {
+        return Err(ParseError::Malformed("body shorter than announced"));
+    }
+    Ok(())
+}
+
+/// Returns whether `byte` may appear in a method or header name.
+fn is_token_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(raw: &str) -> Result<Request, ParseError> {
+        Request::read_from(&mut raw.as_bytes())
+    }
+
+    #[test]
+    fn parses_request_line_query_and_headers() {
+        let request = parse("GET /a%20b?x=1&y=two+words&flag HTTP/1.1\r\nHost: example\r\nAccept: */*\r\n\r\n").unwrap();
+
+        assert_eq!(request.method(), Method::Get);
+        assert_eq!(request.version(), Version::Http11);
+        assert_eq!(request.target(), "/a%20b?x=1&y=two+words&flag");
+        assert_eq!(request.path(), "/a b");
+        assert_eq!(request.query("y"), Some("two words"));
+        assert_eq!(request.query("flag"), Some(""));
+        assert_eq!(request.header("HOST"), Some("example"));
+        assert_eq!(request.headers().len(), 2);
+        assert!(request.body().is_empty());
+    }
+
+    #[test]
+    fn reads_a_chunked_body() {
+        let raw = "POST / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n\
+                   4;ext=1\r\nWiki\r\n5\r\npedia\r\n0\r\nX-Trailer: 1\r\n\r\n";
+
+        assert_eq!(parse(raw).unwrap().body(), b"Wikipedia");
+    }
+
+    #[test]
+    fn limits_the_body_size() {
+        let read = |raw: &str| {
+            let mut raw = raw.as_bytes();
+            let mut request = Request::read_head(&mut raw, MAX_HEAD_SIZE, MAX_HEADERS)?;
+            request.read_body(&mut raw, 8).map(|()| request)
+        };
+
+        let sized = |body: &str, length: usize| {
+            read(&format!("POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: {length}\r\n\r\n{body}"))
+        };
+        assert_eq!(sized("12345678", 8).unwrap().body(), b"12345678");
+        // Refused from the header alone, before the body arrives.
+        assert!(matches!(sized("", 9), Err(ParseError::PayloadTooLarge)));
+
+        let chunked = |chunks: &str| {
+            read(&format!("POST / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n{chunks}0\r\n\r\n"))
+        };
+        assert_eq!(chunked("4\r\nabcd\r\n4\r\nefgh\r\n").unwrap().body(), b"abcdefgh");
+        assert!(matches!(chunked("4\r\nabcd\r\n4\r\nefgh\r\n1\r\ni\r\n"), Err(ParseError::PayloadTooLarge)));
+        assert!(matches!(chunked("ffffffff\r\n"), Err(ParseError::PayloadTooLarge)));
+    }
+
+    #[test]
+    fn leaves_the_next_request_in_the_reader() {
+        let mut raw = "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 3\r\n\r\nabc\
+                       GET /next HTTP/1.1\r\nHost: localhost\r\n\r\n"
+            .as_bytes();
+
+        assert_eq!(Request::read_from(&mut raw).unwrap().body(), b"abc");
+        assert_eq!(Request::read_from(&mut raw).unwrap().path(), "/next");
+        assert!(matches!(Request::read_from(&mut raw), Err(ParseError::Closed)));
+    }
+
+    #[test]
+    fn rejects_malformed_requests() {
+        let cases = [
+            "GET /\r\n\r\n",
+            "GET / HTTP/1.1 extra\r\n\r\n",
+            "GET index.html HTTP/1.1\r\nHost: localhost\r\n\r\n",
+            "GET /%zz HTTP/1.1\r\nHost: localhost\r\n\r\n",
+            "GET / FTP/1.0\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: localhost\r\nNo colon\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\r\n folded\r\n\r\n",
+            "GET / HTTP/1.1\r\nHost: a\r\n",
+            "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: ten\r\n\r\n",
+            "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 10\r\n\r\nshort",
+            "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Length: 1\r\nContent-Length: 2\r\n\r\nab",
+            "POST / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n",
+        ];
+
+        for raw in cases {
+            assert!(matches!(parse(raw), Err(ParseError::Malformed(_))), "{raw:?}");
+        }
+    }
+
+    #[test]
+    fn reports_unsupported_features() {
+        assert!(matches!(parse("BREW /pot HTTP/1.1\r\nHost: localhost\r\n\r\n"), Err(ParseError::NotImplemented(_))));
+        assert!(matches!(parse("GET / HTTP/2.0\r\n\r\n"), Err(ParseError::NotImplemented(_))));
+        assert!(matches!(
+            parse("POST / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: gzip\r\n\r\n"),
+            Err(ParseError::NotImplemented(_))
+        ));
+    }
+
+    #[test]
+    fn empty_input_means_the_connection_closed() {
+        assert!(matches!(parse(""), Err(ParseError::Closed)));
+        assert!(matches!(parse("\r\n"), Err(ParseError::Closed)));
+    }
+
+    #[test]
+    fn requires_a_host_on_http_1_1() {
+        assert!(matches!(parse("GET / HTTP/1.1\r\nAccept: */*\r\n\r\n"), Err(ParseError::Malformed("missing Host header"))));
+        assert_eq!(parse("GET / HTTP/1.1\r\nhost: example\r\n\r\n").unwrap().header("Host"), Some("example"));
+        assert!(parse("GET / HTTP/1.0\r\n\r\n").is_ok());
+    }
+
+    #[test]
+    fn enforces_head_limits() {
+        let head = |raw: &str| Request::read_head(&mut raw.as_bytes(), 64, 3);
+
+        assert!(head("GET / HTTP/1.1\r\nHost: localhost\r\nA: 1\r\nB: 2\r\n\r\n").is_ok());
+        assert!(matches!(
+            head(&format!("GET /{} HTTP/1.1\r\nHost: localhost\r\n\r\n", "a".repeat(64))),
+            Err(ParseError::UriTooLong)
+        ));
+        assert!(matches!(
+            head("GET / HTTP/1.1\r\nHost: localhost\r\nA: 1\r\nB: 2\r\nC: 3\r\n\r\n"),
+            Err(ParseError::HeadersTooLarge)
+        ));
+        assert!(matches!(
+            head(&format!("GET / HTTP/1.1\r\nHost: localhost\r\nA: {}\r\n\r\n", "x".repeat(50))),
+            Err(ParseError::HeadersTooLarge)
+        ));
+        assert!(matches!(head(&"\r\n".repeat(40)), Err(ParseError::UriTooLong)), "blank lines count too");
+        assert!(matches!(
+            parse(&format!(
+                "POST / HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\n\r\n1;{}\r\n",
+                "e".repeat(2000)
+            )),
+            Err(ParseError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn extracts_json_bodies() {
+        let request = |content_type: &str, body: &str| {
+            parse(&format!(
+                "POST / HTTP/1.1\r\nHost: localhost\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\r\n{body}",
+                body.len()
+            ))
+            .unwrap()
+        };
+
+        assert_eq!(request("application/json", "[1, 2]").json::<Vec<u64>>(), Ok(vec![1, 2]));
+        assert_eq!(request("Application/JSON; charset=utf-8", "true").json::<bool>(), Ok(true));
+        assert_eq!(request("application/merge-patch+json", "null").json::<Value>(), Ok(Value::Null));
+
+        assert_eq!(request("text/plain", "[]").json::<Value>(), Err(JsonRejection::UnsupportedMediaType));
+        let untyped = parse("POST / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        assert_eq!(untyped.json::<Value>(), Err(JsonRejection::UnsupportedMediaType));
+        assert!(matches!(request("application/json", "[1,").json::<Value>(), Err(JsonRejection::Invalid(_))));
+        assert!(matches!(request("application/json", "\"x\"").json::<u64>(), Err(JsonRejection::Invalid(_))));
+
+        let response = Response::from(request("application/json", "[").json::<Value>().unwrap_err());
+        assert_eq!(response.status(), StatusCode::UnprocessableEntity);
+        assert_eq!(response.body(), br#"{"error":"invalid JSON body: unexpected end of input at byte 1"}"#);
+    }
+}
@@ -0,0 +1,314 @@
+use std::{
+    fs::File,
+    io::{self, Read, Seek, SeekFrom, Write},
+    ops::Range,
+    sync::Arc,
+};
+
+use super::{Headers, StatusCode};
+use crate::json::ToJson;
+
+/// An HTTP response, put together with builder methods.
+///
+/// `Content-Length` is worked out when the response is written and should
+/// not be set by hand.
+///
+/// The body is either held in memory or, for large files, a [`FileBody`]
+/// that is only read as it is written out.
+///
+/// ```
+/// use server::http::{Response, StatusCode};
+///
+/// let response = Response::new(StatusCode::Created)
+///     .header("Location", "/users/7")
+///     .content_type("application/json")
+///     .with_body(r#"{"id":7}"#);
+///
+/// let mut raw = Vec::new();
+/// response.write_to(&mut raw, false).unwrap();
+/// assert!(raw.starts_with(b"HTTP/1.1 201 Created\r\nLocation: /users/7\r\n"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Response {
+    status: StatusCode,
+    headers: Headers,
+    body: Vec<u8>,
+    file: Option<FileBody>,
+}
+
+/// A body read from an open file while the response is written, a bounded
+/// chunk at a time, so that a large file is never held in memory whole.
+///
+/// It is made of pieces of the file and of bytes put in between them, such
+/// as the part headers of a `multipart/byteranges` body.
+///
+/// ```no_run
+/// use std::fs::File;
+/// use server::http::{FileBody, Response, StatusCode};
+///
+/// let file = File::open("video.mp4")?;
+/// let len = file.metadata()?.len();
+/// let response = Response::new(StatusCode::Ok).with_file(FileBody::new(file).range(0..len));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+#[derive(Debug, Clone)]
+pub struct FileBody {
+    file: Arc<File>,
+    parts: Vec<Part>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Bytes(Vec<u8>),
+    Range(Range<u64>),
+}
+
+impl FileBody {
+    /// An empty body that reads from `file`.
+    pub fn new(file: File) -> Self {
+        FileBody { file: Arc::new(file), parts: Vec::new() }
+    }
+
+    /// Appends the bytes of the file in `range`.
+    pub fn range(mut self, range: Range<u64>) -> Self {
+        self.parts.push(Part::Range(range));
+        self
+    }
+
+    /// Appends `bytes` as they are.
+    pub fn bytes(mut self, bytes: impl Into<Vec<u8>>) -> Self {
+        self.parts.push(Part::Bytes(bytes.into()));
+        self
+    }
+
+    /// The length of the body in bytes.
+    pub fn len(&self) -> u64 {
+        self.parts
+            .iter()
+            .map(|part| match part {
+                Part::Bytes(bytes) => bytes.len() as u64,
+                Part::Range(range) => range.end.saturating_sub(range.start),
+            })
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Copies the body to `writer`. Fails with
+    /// [`io::ErrorKind::UnexpectedEof`] if the file has shrunk.
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let mut file = &*self.file;
+        for part in &self.parts {
+            match part {
+                Part::Bytes(bytes) => writer.write_all(bytes)?,
+                Part::Range(range) => {
+                    let len = range.end.saturating_sub(range.start);
+                    file.seek(SeekFrom::Start(range.start))?;
+                    if io::copy(&mut file.take(len), writer)? < len {
+                        return Err(io::ErrorKind::UnexpectedEof.into());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Equal if they read the same parts of the same open file.
+impl PartialEq for FileBody {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.file, &other.file) && self.parts == other.parts
+    }
+}
+
+impl Eq for FileBody {}
+
+impl Response {
+    /// A response with no headers and an empty body.
+    pub fn new(status: StatusCode) -> Self {
+        Response { status, headers: Headers::new(), body: Vec::new(), file: None }
+    }
+
+    /// A plain text response.
+    pub fn text(status: StatusCode, text: impl Into<String>) -> Self {
+        Response::new(status).content_type("text/plain; charset=utf-8").with_body(text.into())
+    }
+
+    /// An HTML response.
+    pub fn html(status: StatusCode, html: impl Into<String>) -> Self {
+        Response::new(status).content_type("text/html; charset=utf-8").with_body(html.into())
+    }
+
+    /// A JSON response with `value` as the body.
+    pub fn json<T: ToJson + ?Sized>(status: StatusCode, value: &T) -> Self {
+        Response::new(status).content_type("application/json").with_body(value.to_json().to_string())
+    }
+
+    /// A plain text response that just states `status`, such as `404 Not Found`.
+    pub fn error(status: StatusCode) -> Self {
+        Response::text(status, status.to_string())
+    }
+
+    /// A redirect to `location`.
+    pub fn redirect(status: StatusCode, location: impl Into<String>) -> Self {
+        Response::new(status).header("Location", location)
+    }
+
+    /// Adds a header field, keeping any others with the same name.
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    /// Sets `Content-Type`, replacing any previous value.
+    pub fn content_type(mut self, content_type: impl Into<String>) -> Self {
+        self.headers.set("Content-Type", content_type);
+        self
+    }
+
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into();
+        self.file = None;
+        self
+    }
+
+    /// Sends `file` as the body, replacing any other.
+    pub fn with_file(mut self, file: FileBody) -> Self {
+        self.body = Vec::new();
+        self.file = Some(file);
+        self
+    }
+
+    pub fn with_status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn headers(&self) -> &Headers {
+        &self.headers
+    }
+
+    pub fn headers_mut(&mut self) -> &mut Headers {
+        &mut self.headers
+    }
+
+    /// The body held in memory, which is empty if the body is a [`FileBody`].
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    pub fn into_body(self) -> Vec<u8> {
+        self.body
+    }
+
+    pub fn file_body(&self) -> Option<&FileBody> {
+        self.file.as_ref()
+    }
+
+    /// The length of the body in bytes, wherever it is kept.
+    pub fn body_len(&self) -> u64 {
+        self.file.as_ref().map_or(self.body.len() as u64, FileBody::len)
+    }
+
+    /// Writes the response, adding `Content-Length`. With `head_only` the
+    /// body is left out, as for a `HEAD` request.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if a header contains a line
+    /// break, which would let it smuggle in more headers.
+    pub fn write_to<W: Write>(&self, writer: &mut W, head_only: bool) -> io::Result<()> {
+        let mut head = format!("HTTP/1.1 {}\r\n", self.status);
+
+        for (name, value) in self.headers.iter() {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                continue;
+            }
+            if [name, value].iter().any(|s| s.contains(['\r', '\n'])) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("line break in header {name:?}")));
+            }
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+
+        let has_body = self.status.allows_body();
+        if has_body {
+            head.push_str(&format!("Content-Length: {}\r\n", self.body_len()));
+        }
+        head.push_str("\r\n");
+
+        writer.write_all(head.as_bytes())?;
+        if has_body && !head_only {
+            match &self.file {
+                Some(file) => file.write_to(writer)?,
+                None => writer.write_all(&self.body)?,
+            }
+        }
+        writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn serialize(response: &Response, head_only: bool) -> String {
+        let mut raw = Vec::new();
+        response.write_to(&mut raw, head_only).unwrap();
+        String::from_utf8(raw).unwrap()
+    }
+
+    #[test]
+    fn writes_status_headers_and_body() {
+        let response = Response::text(StatusCode::NotFound, "gone").header("X-Trace", "1");
+
+        assert_eq!(
+            serialize(&response, false),
+            "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain; charset=utf-8\r\nX-Trace: 1\r\nContent-Length: 4\r\n\r\ngone"
+        );
+        assert!(serialize(&response, true).ends_with("Content-Length: 4\r\n\r\n"));
+    }
+
+    #[test]
+    fn content_length_is_always_computed() {
+        let response = Response::new(StatusCode::Ok).header("Content-Length", "99").with_body(vec![0, 159, 146, 150]);
+
+        let mut raw = Vec::new();
+        response.write_to(&mut raw, false).unwrap();
+        assert!(raw.ends_with(b"Content-Length: 4\r\n\r\n\x00\x9f\x92\x96"));
+    }
+
+    #[test]
+    fn no_content_has_no_body() {
+        let response = Response::new(StatusCode::NoContent).with_body("ignored");
+
+        assert_eq!(serialize(&response, false), "HTTP/1.1 204 No Content\r\n\r\n");
+    }
+
+    #[test]
+    fn streams_file_bodies() {
+        let path = std::env::temp_dir().join(format!("server-response-{}", std::process::id()));
+        std::fs::write(&path, "0123456789").unwrap();
+        let body = FileBody::new(File::open(&path).unwrap()).bytes("[").range(2..5).bytes("|").range(8..10).bytes("]");
+
+        let response = Response::new(StatusCode::Ok).with_file(body);
+        assert_eq!(response.body_len(), 8);
+        assert!(response.body().is_empty());
+        assert_eq!(serialize(&response, false), "HTTP/1.1 200 OK\r\nContent-Length: 8\r\n\r\n[234|89]");
+
+        let short = Response::new(StatusCode::Ok).with_file(response.file_body().unwrap().clone().range(8..20));
+        assert_eq!(short.write_to(&mut Vec::new(), false).unwrap_err().kind(), io::ErrorKind::UnexpectedEof);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn refuses_header_injection() {
+        let response = Response::new(StatusCode::Ok).header("Location", "/\r\nSet-Cookie: evil=1");
+
+        let error = response.write_to(&mut Vec::new(), false).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,295 @@
+use std::sync::Arc;
+
+use super::{Method, Middleware, Next, Request, Response, StatusCode};
+
+/// Something that answers requests, usually a closure `|req: &Request| -> Response`.
+pub trait Handler: Send + Sync {
+    fn handle(&self, request: &Request) -> Response;
+}
+
+impl<F> Handler for F
+where F: Fn(&Request) -> Response + Send + Sync {
+    fn handle(&self, request: &Request) -> Response {
+        self(request)
+    }
+}
+
+impl Handler for Box<dyn Handler> {
+    fn handle(&self, request: &Request) -> Response {
+        (**self).handle(request)
+    }
+}
+
+impl Handler for Arc<dyn Handler> {
+    fn handle(&self, request: &Request) -> Response {
+        (**self).handle(request)
+    }
+}
+
+/// Picks a [`Handler`] by method and path.
+///
+/// Patterns are matched segment by segment. `:name` matches any single
+/// non-empty segment and `*name` (or just `*`) matches the rest of the path,
+/// and either is available to the handler through [`Request::param`]. When
+/// several patterns match, the one registered first wins.
+///
+/// A path that no pattern matches gets a 404 response, and a path that only
+/// matches routes for other methods gets a 405 response with an `Allow`
+/// header. `GET` routes also answer `HEAD` requests.
+///
+/// Every request, routed or not, first goes through the [`Middleware`]s
+/// added with [`wrap`](Router::wrap).
+///
+/// ```
+/// use server::http::{Request, Response, Router, StatusCode};
+///
+/// let mut router = Router::new();
+/// router.get("/users/:id", |req: &Request| Response::text(StatusCode::Ok, format!("user {}", req.param("id").unwrap())));
+///
+/// let mut request = Request::read_from(&mut &b"GET /users/7 HTTP/1.1\r\nHost: localhost\r\n\r\n"[..]).unwrap();
+/// assert_eq!(router.dispatch(&mut request).body(), b"user 7");
+///
+/// let mut request = Request::read_from(&mut &b"DELETE /users/7 HTTP/1.1\r\nHost: localhost\r\n\r\n"[..]).unwrap();
+/// let response = router.dispatch(&mut request);
+/// assert_eq!(response.status(), StatusCode::MethodNotAllowed);
+/// assert_eq!(response.headers().get("Allow"), Some("GET, HEAD"));
+/// ```
+#[derive(Default)]
+pub struct Router {
+    routes: Vec<Route>,
+    not_found: Option<Box<dyn Handler>>,
+    middleware: Vec<Box<dyn Middleware>>,
+}
+
+struct Route {
+    method: Method,
+    pattern: Vec<Segment>,
+    handler: Box<dyn Handler>,
+}
+
+enum Segment {
+    Static(String),
+    Param(String),
+    Rest(String),
+}
+
+impl Router {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `handler` for `method` requests whose path matches `pattern`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `pattern` does not start with `/`, or has a wildcard
+    /// segment anywhere but at the end.
+    pub fn route<H>(&mut self, method: Method, pattern: &str, handler: H) -> &mut Self
+    where H: Handler + 'static {
+        self.routes.push(Route { method, pattern: parse_pattern(pattern), handler: Box::new(handler) });
+        self
+    }
+
+    pub fn get<H: Handler + 'static>(&mut self, pattern: &str, handler: H) -> &mut Self {
+        self.route(Method::Get, pattern, handler)
+    }
+
+    pub fn post<H: Handler + 'static>(&mut self, pattern: &str, handler: H) -> &mut Self {
+        self.route(Method::Post, pattern, handler)
+    }
+
+    pub fn put<H: Handler + 'static>(&mut self, pattern: &str, handler: H) -> &mut Self {
+        self.route(Method::Put, pattern, handler)
+    }
+
+    pub fn patch<H: Handler + 'static>(&mut self, pattern: &str, handler: H) -> &mut Self {
+        self.route(Method::Patch, pattern, handler)
+    }
+
+    pub fn delete<H: Handler + 'static>(&mut self, pattern: &str, handler: H) -> &mut Self {
+        self.route(Method::Delete, pattern, handler)
+    }
+
+    /// Replaces the default 404 response for paths no route matches.
+    pub fn not_found<H: Handler + 'static>(&mut self, handler: H) -> &mut Self {
+        self.not_found = Some(Box::new(handler));
+        self
+    }
+
+    /// Adds `middleware` around the routes. Middlewares run in the order they
+    /// were added, so the first one sees the request first and the response
+    /// last.
+    pub fn wrap<M: Middleware + 'static>(&mut self, middleware: M) -> &mut Self {
+        self.middleware.push(Box::new(middleware));
+        self
+    }
+
+    /// Passes `request` through the middlewares to the handler of its route,
+    /// which finds the path parameters of the matched pattern in it.
+    pub fn dispatch(&self, request: &mut Request) -> Response {
+        Next::new(&self.middleware, self).run(request)
+    }
+
+    /// Runs the handler for `request`, after storing the path parameters of
+    /// the matched pattern in it.
+    pub(super) fn run_route(&self, request: &mut Request) -> Response {
+        let segments: Vec<&str> = request.path().strip_prefix('/').unwrap_or_default().split('/').collect();
+        let mut allowed = Vec::new();
+        let mut fallback = None;
+
+        for route in &self.routes {
+            let Some(params) = match_pattern(&route.pattern, &segments) else {
+                continue;
+            };
+            if route.method == request.method() {
+                request.set_params(params);
+                return route.handler.handle(request);
+            }
+            if route.method == Method::Get && request.method() == Method::Head && fallback.is_none() {
+                fallback = Some((route, params));
+            }
+            if !allowed.contains(&route.method) {
+                allowed.push(route.method);
+            }
+        }
+
+        if let Some((route, params)) = fallback {
+            request.set_params(params);
+            return route.handler.handle(request);
+        }
+
+        if allowed.is_empty() {
+            return match &self.not_found {
+                Some(handler) => handler.handle(request),
+                None => Response::error(StatusCode::NotFound),
+            };
+        }
+
+        if allowed.contains(&Method::Get) && !allowed.contains(&Method::Head) {
+            let get = allowed.iter().position(|&method| method == Method::Get).unwrap_or_default();
+            allowed.insert(get + 1, Method::Head);
+        }
+        let allow = allowed.iter().map(|method| method.as_str()).collect::<Vec<_>>().join(", ");
+        Response::error(StatusCode::MethodNotAllowed).header("Allow", allow)
+    }
+}
+
+fn parse_pattern(pattern: &str) -> Vec<Segment> {
+    let Some(rest) = pattern.strip_prefix('/') else {
+        panic!("route pattern {pattern:?} must start with '/'");
+    };
+
+    let parts: Vec<&str> = rest.split('/').collect();
+    let last = parts.len() - 1;
+
+    parts
+        .iter()
+        .enumerate()
+        .map(|(i, part)| {
+            if let Some(name) = part.strip_prefix(':') {
+                Segment::Param(name.to_string())
+            } else if let Some(name) = part.strip_prefix('*') {
+                assert!(i == last, "wildcard must be the last segment of route pattern {pattern:?}");
+                Segment::Rest(if name.is_empty() { "*" } else { name }.to_string())
+            } else {
+                Segment::Static(part.to_string())
+            }
+        })
+        .collect()
+}
+
+/// Returns the parameters captured by `pattern` if it matches the path `segments`.
+fn match_pattern(pattern: &[Segment], segments: &[&str]) -> Option<Vec<(String, String)>> {
+    let mut params = Vec::new();
+
+    for (i, segment) in pattern.iter().enumerate() {
+        match segment {
+            Segment::Rest(name) => {
+                params.push((name.clone(), segments.get(i..).unwrap_or_default().join("/")));
+                return Some(params);
+            }
+            Segment::Static(expected) if segments.get(i) != Some(&expected.as_str()) => return None,
+            Segment::Static(_) => {}
+            Segment::Param(name) => match segments.get(i) {
+                Some(value) if !value.is_empty() => params.push((name.clone(), value.to_string())),
+                _ => return None,
+            },
+        }
+    }
+
+    (pattern.len() == segments.len()).then_some(params)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(method: &str, path: &str) -> Request {
+        Request::read_from(&mut format!("{method} {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").as_bytes()).unwrap()
+    }
+
+    fn echo(name: &'static str) -> impl Handler {
+        move |req: &Request| {
+            let params: Vec<String> = req.params().iter().map(|(key, value)| format!("{key}={value}")).collect();
+            Response::text(StatusCode::Ok, format!("{name} {}", params.join(" ")).trim_end())
+        }
+    }
+
+    fn body(router: &Router, method: &str, path: &str) -> String {
+        String::from_utf8(router.dispatch(&mut request(method, path)).body().to_vec()).unwrap()
+    }
+
+    #[test]
+    fn matches_static_param_and_wildcard_segments() {
+        let mut router = Router::new();
+        router
+            .get("/", echo("root"))
+            .get("/users/new", echo("new"))
+            .get("/users/:id", echo("user"))
+            .get("/users/:id/posts/:post", echo("post"))
+            .get("/static/*path", echo("static"));
+
+        assert_eq!(body(&router, "GET", "/"), "root");
+        assert_eq!(body(&router, "GET", "/users/new"), "new");
+        assert_eq!(body(&router, "GET", "/users/42"), "user id=42");
+        assert_eq!(body(&router, "GET", "/users/42/posts/7"), "post id=42 post=7");
+        assert_eq!(body(&router, "GET", "/static/css/site.css"), "static path=css/site.css");
+        assert_eq!(router.dispatch(&mut request("GET", "/users/")).status(), StatusCode::NotFound);
+        assert_eq!(router.dispatch(&mut request("GET", "/users/42/extra")).status(), StatusCode::NotFound);
+    }
+
+    #[test]
+    fn wrong_method_gets_405_with_allow() {
+        let mut router = Router::new();
+        router.get("/items", echo("list")).post("/items", echo("create"));
+
+        let response = router.dispatch(&mut request("DELETE", "/items"));
+
+        assert_eq!(response.status(), StatusCode::MethodNotAllowed);
+        assert_eq!(response.headers().get("Allow"), Some("GET, HEAD, POST"));
+        assert_eq!(body(&router, "HEAD", "/items"), "list");
+    }
+
+    #[test]
+    fn custom_not_found_handler() {
+        let mut router = Router::new();
+        router.not_found(|_: &Request| Response::text(StatusCode::NotFound, "nothing here"));
+
+        assert_eq!(body(&router, "GET", "/missing"), "nothing here");
+    }
+
+    #[test]
+    fn boxed_handlers_can_be_registered() {
+        let handler: Box<dyn Handler> = Box::new(echo("boxed"));
+        let mut router = Router::new();
+        router.get("/", handler);
+
+        assert_eq!(body(&router, "GET", "/"), "boxed");
+    }
+
+    #[test]
+    #[should_panic(expected = "wildcard must be the last segment")]
+    fn wildcard_must_come_last() {
+        Router::new().get("/*rest/more", echo("bad"));
+    }
+}
@@ -0,0 +1,528 @@
+use std::{
+    fs::{File, Metadata},
+    io,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering::Relaxed},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use super::{
+    date::{format_http_date, parse_http_date},
+    range::{parse_ranges, ByteRanges},
+    FileBody, Handler, Method, Request, Response, StatusCode,
+};
+
+/// Default size above which a body is streamed from the file rather than
+/// read into memory.
+const BUFFER_LIMIT: u64 = 1024 * 1024;
+
+/// A [`Handler`] that serves files from a document root.
+///
+/// Mounted on a pattern ending in `*`, it serves the part of the path the
+/// wildcard matched; otherwise it serves the whole request path. Paths that
+/// would leave the document root, through `..` or a symbolic link, get a 404
+/// response like any missing file. A directory is served through its first
+/// index file that exists.
+///
+/// Files are sent with an `ETag` and a `Last-Modified` header, and with a
+/// `304 Not Modified` response when the client's `If-None-Match` or
+/// `If-Modified-Since` shows that its copy is still current.
+///
+/// Byte ranges are supported: a `Range` header gets a `206 Partial Content`
+/// response, as `multipart/byteranges` if it asks for several ranges, or a
+/// `416 Range Not Satisfiable` response if none of them is inside the file.
+///
+/// Bodies up to [`buffer_limit`](Self::buffer_limit) are read into memory,
+/// where middleware such as [`Compression`](super::Compression) can work on
+/// them. Larger ones are streamed from the file as a [`FileBody`].
+///
+/// ```no_run
+/// use server::http::{Router, StaticFiles};
+///
+/// let mut router = Router::new();
+/// router.get(
+///     "/assets/*",
+///     StaticFiles::new("public").index_files(&["index.html", "index.htm"]).cache_control("public, max-age=3600"),
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct StaticFiles {
+    root: PathBuf,
+    index_files: Vec<String>,
+    cache_control: Option<String>,
+    buffer_limit: u64,
+}
+
+impl StaticFiles {
+    /// Serves the files under `root`, with `index.html` as the index file
+    /// and `Cache-Control: no-cache`, so that clients revalidate every time.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        StaticFiles {
+            root: root.into(),
+            index_files: vec!["index.html".to_string()],
+            cache_control: Some("no-cache".to_string()),
+            buffer_limit: BUFFER_LIMIT,
+        }
+    }
+
+    /// Sets the file names tried, in order, when a directory is requested.
+    pub fn index_files(mut self, names: &[&str]) -> Self {
+        self.index_files = names.iter().map(|name| name.to_string()).collect();
+        self
+    }
+
+    /// Sets the `Cache-Control` header sent with every file.
+    pub fn cache_control(mut self, value: impl Into<String>) -> Self {
+        self.cache_control = Some(value.into());
+        self
+    }
+
+    /// Leaves out the `Cache-Control` header, leaving caching up to the client.
+    pub fn no_cache_control(mut self) -> Self {
+        self.cache_control = None;
+        self
+    }
+
+    /// Bodies larger than this are streamed from the file instead of being
+    /// read into memory. Defaults to 1 MiB.
+    pub fn buffer_limit(mut self, bytes: u64) -> Self {
+        self.buffer_limit = bytes;
+        self
+    }
+
+    /// Maps a URL path to a path under the root, or `None` if it tries to leave it.
+    fn resolve(&self, url_path: &str) -> Option<PathBuf> {
+        let mut path = self.root.clone();
+
+        for segment in url_path.split('/') {
+            match segment {
+                "" | "." => {}
+                ".." => return None,
+                _ if segment.contains(['\\', '\0']) => return None,
+                _ => path.push(segment),
+            }
+        }
+
+        Some(path)
+    }
+
+    /// Returns `path` if it exists inside the root once symbolic links are
+    /// followed, or, for a directory, its first index file.
+    fn find_file(&self, path: &Path) -> io::Result<PathBuf> {
+        let root = self.root.canonicalize()?;
+        let path = path.canonicalize()?;
+        if !path.starts_with(&root) {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        if !path.is_dir() {
+            return Ok(path);
+        }
+
+        self.index_files
+            .iter()
+            .map(|name| path.join(name))
+            .find(|index| index.is_file())
+            .ok_or_else(|| io::ErrorKind::NotFound.into())
+    }
+
+    fn serve(&self, request: &Request, path: &Path) -> io::Result<Response> {
+        let file = File::open(path)?;
+        let metadata = file.metadata()?;
+        let modified = metadata.modified().ok();
+        let etag = entity_tag(&metadata);
+
+        let mut response = Response::new(StatusCode::Ok).header("ETag", &etag);
+        if let Some(modified) = modified {
+            response = response.header("Last-Modified", format_http_date(modified));
+        }
+        if let Some(cache_control) = &self.cache_control {
+            response = response.header("Cache-Control", cache_control);
+        }
+
+        let content_type = content_type(path);
+        if is_not_modified(request, &etag, modified) {
+            // The type lets a compressing middleware describe the 304 as it
+            // would the full response.
+            return Ok(response.with_status(StatusCode::NotModified).content_type(content_type));
+        }
+
+        let response = response.header("Accept-Ranges", "bytes");
+        let len = metadata.len();
+
+        let ranges = match request.header("Range") {
+            Some(range) if if_range_matches(request, &etag, modified) => parse_ranges(range, len),
+            _ => ByteRanges::Ignored,
+        };
+
+        let ranges = match ranges {
+            ByteRanges::Ignored => {
+                return self.with_body(response.content_type(content_type), FileBody::new(file).range(0..len));
+            }
+            ByteRanges::Unsatisfiable => {
+                return Ok(response
+                    .with_status(StatusCode::RangeNotSatisfiable)
+                    .header("Content-Range", format!("bytes */{len}")));
+            }
+            ByteRanges::Satisfiable(ranges) => ranges,
+        };
+
+        let response = response.with_status(StatusCode::PartialContent);
+
+        if let [range] = &ranges[..] {
+            let response = response.header("Content-Range", content_range(range, len)).content_type(content_type);
+            return self.with_body(response, FileBody::new(file).range(range.clone()));
+        }
+
+        let boundary = multipart_boundary();
+        let mut body = FileBody::new(file);
+        for range in ranges {
+            let content_range = content_range(&range, len);
+            body = body
+                .bytes(format!("--{boundary}\r\nContent-Type: {content_type}\r\nContent-Range: {content_range}\r\n\r\n"))
+                .range(range)
+                .bytes("\r\n");
+        }
+        body = body.bytes(format!("--{boundary}--\r\n"));
+
+        self.with_body(response.content_type(format!("multipart/byteranges; boundary={boundary}")), body)
+    }
+
+    /// Gives `response` the body, read into memory unless it is over the
+    /// buffer limit.
+    fn with_body(&self, response: Response, body: FileBody) -> io::Result<Response> {
+        if body.len() > self.buffer_limit {
+            return Ok(response.with_file(body));
+        }
+        let mut bytes = Vec::new();
+        body.write_to(&mut bytes)?;
+        Ok(response.with_body(bytes))
+    }
+}
+
+impl Handler for StaticFiles {
+    fn handle(&self, request: &Request) -> Response {
+        let url_path = request.param("*").unwrap_or(request.path());
+        let Some(path) = self.resolve(url_path) else {
+            return Response::error(StatusCode::NotFound);
+        };
+
+        // Relative links in an index page only work below a trailing slash.
+        let target = request.target().split('?').next().unwrap_or_default();
+        if path.is_dir() && !target.ends_with('/') {
+            return Response::redirect(StatusCode::MovedPermanently, format!("{target}/"));
+        }
+
+        match self.find_file(&path).and_then(|file| self.serve(request, &file)) {
+            Ok(response) => response,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Response::error(StatusCode::NotFound),
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => Response::error(StatusCode::Forbidden),
+            Err(_) => Response::error(StatusCode::InternalServerError),
+        }
+    }
+}
+
+/// A validator that changes whenever the file's size or modification time does.
+fn entity_tag(metadata: &Metadata) -> String {
+    let modified = metadata.modified().ok().and_then(|time| time.duration_since(UNIX_EPOCH).ok()).unwrap_or_default();
+    format!("\"{:x}.{:x}-{:x}\"", modified.as_secs(), modified.subsec_nanos(), metadata.len())
+}
+
+/// Whether the client's cached copy, as described by its conditional
+/// headers, is still current. `If-None-Match` wins over `If-Modified-Since`.
+fn is_not_modified(request: &Request, etag: &str, modified: Option<SystemTime>) -> bool {
+    if !matches!(request.method(), Method::Get | Method::Head) {
+        return false;
+    }
+
+    if let Some(tags) = request.header("If-None-Match") {
+        // Weak comparison: `W/"x"` matches `"x"`.
+        let etag = etag.trim_start_matches("W/");
+        return tags.trim() == "*" || tags.split(',').any(|tag| tag.trim().trim_start_matches("W/") == etag);
+    }
+
+    let since = request.header("If-Modified-Since").and_then(parse_http_date);
+    match (since, modified) {
+        // HTTP dates have whole seconds, so compare at that precision.
+        (Some(since), Some(modified)) => {
+            let secs = |time: SystemTime| time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+            secs(modified) <= secs(since)
+        }
+        _ => false,
+    }
+}
+
+/// Whether a `Range` header applies: there is no `If-Range`, or it names
+/// the current version of the file. Only a strong entity tag or the exact
+/// modification time counts.
+fn if_range_matches(request: &Request, etag: &str, modified: Option<SystemTime>) -> bool {
+    let Some(validator) = request.header("If-Range").map(str::trim) else {
+        return true;
+    };
+
+    if validator.starts_with('"') {
+        return validator == etag;
+    }
+    match (parse_http_date(validator), modified) {
+        (Some(date), Some(modified)) => {
+            date.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs())
+                == modified.duration_since(UNIX_EPOCH).ok().map(|d| d.as_secs())
+        }
+        _ => false,
+    }
+}
+
+fn content_range(range: &Range<u64>, len: u64) -> String {
+    format!("bytes {}-{}/{len}", range.start, range.end - 1)
+}
+
+/// A separator for `multipart/byteranges` bodies that is very unlikely to
+/// appear in the file.
+fn multipart_boundary() -> String {
+    static NEXT: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().subsec_nanos();
+    format!("byteranges-{nanos:08x}{:08x}", NEXT.fetch_add(1, Relaxed))
+}
+
+/// Guesses the media type of a file from its extension.
+fn content_type(path: &Path) -> &'static str {
+    let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+
+    match extension.to_ascii_lowercase().as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "csv" => "text/csv; charset=utf-8",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "wasm" => "application/wasm",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::http::Router;
+
+    /// A fresh directory under the system temp directory, removed on drop.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            static NEXT: AtomicUsize = AtomicUsize::new(0);
+            let id = NEXT.fetch_add(1, Ordering::SeqCst);
+            let path = std::env::temp_dir().join(format!("server-static-{}-{id}", std::process::id()));
+            fs::create_dir_all(&path).unwrap();
+            TempDir(path)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn get(router: &Router, path: &str) -> Response {
+        get_with(router, path, "")
+    }
+
+    fn get_with(router: &Router, path: &str, headers: &str) -> Response {
+        let raw = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n{headers}\r\n");
+        router.dispatch(&mut Request::read_from(&mut raw.as_bytes()).unwrap())
+    }
+
+    fn site() -> (TempDir, Router) {
+        let dir = TempDir::new();
+        let root = dir.0.join("root");
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::write(root.join("style.css"), "body {}").unwrap();
+        fs::write(root.join("logo.png"), [0x89, b'P', b'N', b'G', 0, 0xff]).unwrap();
+        fs::write(root.join("docs/index.html"), "<h1>docs</h1>").unwrap();
+        fs::write(dir.0.join("secret.txt"), "secret").unwrap();
+
+        let mut router = Router::new();
+        router.get("/static/*", StaticFiles::new(&root));
+        (dir, router)
+    }
+
+    #[test]
+    fn serves_files_with_a_content_type() {
+        let (_dir, router) = site();
+
+        let response = get(&router, "/static/style.css");
+        assert_eq!(response.status(), StatusCode::Ok);
+        assert_eq!(response.body(), b"body {}");
+        assert_eq!(response.headers().get("Content-Type"), Some("text/css; charset=utf-8"));
+
+        let response = get(&router, "/static/logo.png");
+        assert_eq!(response.body(), [0x89, b'P', b'N', b'G', 0, 0xff]);
+        assert_eq!(response.headers().get("Content-Type"), Some("image/png"));
+    }
+
+    #[test]
+    fn serves_directory_index_files() {
+        let (_dir, router) = site();
+
+        assert_eq!(get(&router, "/static/docs/").body(), b"<h1>docs</h1>");
+
+        let response = get(&router, "/static/docs");
+        assert_eq!(response.status(), StatusCode::MovedPermanently);
+        assert_eq!(response.headers().get("Location"), Some("/static/docs/"));
+
+        assert_eq!(get(&router, "/static/").status(), StatusCode::NotFound);
+    }
+
+    #[test]
+    fn refuses_to_leave_the_root() {
+        let (_dir, router) = site();
+
+        assert_eq!(get(&router, "/static/../secret.txt").status(), StatusCode::NotFound);
+        assert_eq!(get(&router, "/static/%2e%2e/secret.txt").status(), StatusCode::NotFound);
+        assert_eq!(get(&router, "/static/docs/..%2f..%2fsecret.txt").status(), StatusCode::NotFound);
+        assert_eq!(get(&router, "/static/missing.txt").status(), StatusCode::NotFound);
+    }
+
+    #[test]
+    fn answers_conditional_requests_with_304() {
+        let (_dir, router) = site();
+
+        let response = get(&router, "/static/style.css");
+        let etag = response.headers().get("ETag").unwrap();
+        let modified = response.headers().get("Last-Modified").unwrap();
+        assert_eq!(response.headers().get("Cache-Control"), Some("no-cache"));
+
+        let response = get_with(&router, "/static/style.css", &format!("If-None-Match: \"other\", W/{etag}\r\n"));
+        assert_eq!(response.status(), StatusCode::NotModified);
+        assert_eq!(response.headers().get("ETag"), Some(etag));
+        assert_eq!(response.headers().get("Content-Type"), Some("text/css; charset=utf-8"));
+        assert!(response.body().is_empty());
+
+        let response = get_with(&router, "/static/style.css", &format!("If-Modified-Since: {modified}\r\n"));
+        assert_eq!(response.status(), StatusCode::NotModified);
+
+        let response = get_with(&router, "/static/style.css", "If-Modified-Since: Thu, 01 Jan 1970 00:00:00 GMT\r\n");
+        assert_eq!(response.status(), StatusCode::Ok);
+
+        // A non-matching If-None-Match overrides a matching If-Modified-Since.
+        let headers = format!("If-None-Match: \"other\"\r\nIf-Modified-Since: {modified}\r\n");
+        assert_eq!(get_with(&router, "/static/style.css", &headers).status(), StatusCode::Ok);
+    }
+
+    #[test]
+    fn cache_control_is_configurable() {
+        let (dir, _) = site();
+        let mut router = Router::new();
+        router
+            .get("/long/*", StaticFiles::new(dir.0.join("root")).cache_control("public, max-age=86400"))
+            .get("/none/*", StaticFiles::new(dir.0.join("root")).no_cache_control());
+
+        let response = get(&router, "/long/style.css");
+        assert_eq!(response.headers().get("Cache-Control"), Some("public, max-age=86400"));
+        assert!(!get(&router, "/none/style.css").headers().contains("Cache-Control"));
+    }
+
+    #[test]
+    fn serves_a_single_range() {
+        let (_dir, router) = site();
+
+        let response = get_with(&router, "/static/style.css", "Range: bytes=2-4\r\n");
+        assert_eq!(response.status(), StatusCode::PartialContent);
+        assert_eq!(response.headers().get("Content-Range"), Some("bytes 2-4/7"));
+        assert_eq!(response.headers().get("Accept-Ranges"), Some("bytes"));
+        assert_eq!(response.body(), b"dy ");
+
+        let response = get_with(&router, "/static/style.css", "Range: bytes=-2\r\n");
+        assert_eq!(response.body(), b"{}");
+    }
+
+    #[test]
+    fn serves_several_ranges_as_multipart() {
+        let (_dir, router) = site();
+
+        let response = get_with(&router, "/static/style.css", "Range: bytes=0-1, 5-\r\n");
+        assert_eq!(response.status(), StatusCode::PartialContent);
+
+        let content_type = response.headers().get("Content-Type").unwrap();
+        let boundary = content_type.strip_prefix("multipart/byteranges; boundary=").unwrap();
+        let expected = format!(
+            "--{boundary}\r\nContent-Type: text/css; charset=utf-8\r\nContent-Range: bytes 0-1/7\r\n\r\nbo\r\n\
+             --{boundary}\r\nContent-Type: text/css; charset=utf-8\r\nContent-Range: bytes 5-6/7\r\n\r\n{{}}\r\n\
+             --{boundary}--\r\n"
+        );
+        assert_eq!(String::from_utf8(response.body().to_vec()).unwrap(), expected);
+    }
+
+    #[test]
+    fn unsatisfiable_ranges_get_416() {
+        let (_dir, router) = site();
+
+        let response = get_with(&router, "/static/style.css", "Range: bytes=7-\r\n");
+        assert_eq!(response.status(), StatusCode::RangeNotSatisfiable);
+        assert_eq!(response.headers().get("Content-Range"), Some("bytes */7"));
+    }
+
+    #[test]
+    fn streams_files_over_the_buffer_limit() {
+        let (dir, _) = site();
+        let contents: Vec<u8> = (0..64 * 1024).map(|n| (n % 251) as u8).collect();
+        fs::write(dir.0.join("root/big.bin"), &contents).unwrap();
+        let mut router = Router::new();
+        router.get("/static/*", StaticFiles::new(dir.0.join("root")).buffer_limit(1024));
+
+        let body = |response: &Response| {
+            let mut raw = Vec::new();
+            response.file_body().expect("a streamed body").write_to(&mut raw).unwrap();
+            raw
+        };
+
+        let response = get(&router, "/static/big.bin");
+        assert!(response.body().is_empty());
+        assert_eq!(response.body_len(), contents.len() as u64);
+        assert_eq!(body(&response), contents);
+
+        let response = get_with(&router, "/static/big.bin", "Range: bytes=1000-\r\n");
+        assert_eq!(response.status(), StatusCode::PartialContent);
+        assert_eq!(body(&response), &contents[1000..]);
+
+        let response = get_with(&router, "/static/big.bin", "Range: bytes=0-0, 2000-\r\n");
+        let raw = body(&response);
+        let boundary = response.headers().get("Content-Type").unwrap().rsplit('=').next().unwrap();
+        assert_eq!(raw.len() as u64, response.body_len());
+        assert!(raw.ends_with(&[&contents[contents.len() - 10..], format!("\r\n--{boundary}--\r\n").as_bytes()].concat()));
+
+        // Small files stay in memory.
+        assert_eq!(get(&router, "/static/style.css").body(), b"body {}");
+        assert!(get(&router, "/static/style.css").file_body().is_none());
+    }
+
+    #[test]
+    fn stale_if_range_sends_the_whole_file() {
+        let (_dir, router) = site();
+        let etag = get(&router, "/static/style.css").headers().get("ETag").unwrap().to_string();
+
+        let response = get_with(&router, "/static/style.css", &format!("Range: bytes=0-1\r\nIf-Range: {etag}\r\n"));
+        assert_eq!(response.status(), StatusCode::PartialContent);
+
+        let response = get_with(&router, "/static/style.css", "Range: bytes=0-1\r\nIf-Range: \"old\"\r\n");
+        assert_eq!(response.status(), StatusCode::Ok);
+        assert_eq!(response.body(), b"body {}");
+    }
+}
@@ -1,74 +1,11 @@
-use std::{sync::{mpsc, Arc, Mutex}, thread};
-
-type Job = Box<dyn FnOnce() + Send + 'static>;
-
-pub struct ThreadPool {
-    sender: Option<mpsc::Sender<Job>>,
-    workers: Vec<Worker>
-}
-
-impl ThreadPool {
-    pub fn new(size: usize) -> Self {
-        assert!(size > 0);
-
-        let (sender, receiver) = mpsc::channel();
-        
-        let receiver = Arc::new(Mutex::new(receiver));
-
-        let mut workers = Vec::with_capacity(size);
-
-        for id in 0..size {
-            workers.push(Worker::new(id, Arc::clone(&receiver)));
-        }
-
-        ThreadPool { sender: Some(sender), workers: workers }
-    }
-
-    pub fn execute<F>(&self, f: F) 
-    where F: FnOnce() + Send + 'static {
-        let job = Box::new(f);
-
-        self.sender.as_ref().unwrap().send(job).unwrap();
-    }
-}
-
-impl Drop for ThreadPool {
-    fn drop(&mut self) {
-        drop(self.sender.take());
-
-        for worker in self.workers.drain(..) {
-            println!("Shutting down worker: {}", worker.id);
-
-            worker.thread.join().unwrap();
-        }
-    }
-}
-
-struct Worker {
-    id: usize,
-    thread: thread::JoinHandle<()>
-}
-
-impl Worker {
-    fn new(id: usize, receiver: Arc<Mutex<mpsc::Receiver<Job>>>) -> Self {
-        let thread = thread::spawn(move ||{
-            loop { 
-                let message = receiver.lock().unwrap().recv();
-
-                match message {
-                    Ok(job) => {
-                        println!("Worker {id} got a job; executing.");
-
-                        job();
-                    }
-                    Err(_) => {
-                        println!("Worker {id} disconnected; shutting down.");
-                        break;
-                    }
-                }
-            }
-        });
-
-        Worker { id: id, thread: thread }
-    }
-}
\ No newline at end of file
+pub mod config;
+pub mod http;
+pub mod json;
+pub mod log;
+mod pool;
+pub mod signal;
+
+pub use pool::{
+    panic_message, Builder, ExecuteError, MetricsSnapshot, PoolCreationError, PoolEvent, PoolMetrics,
+    PoolObserver, Priority, RejectionPolicy, ScheduledHandle, Scope, ShutdownReport, ThreadPool, WorkerStats,
+};
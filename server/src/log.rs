@@ -0,0 +1,61 @@
+use std::{fmt, io::Write, sync::Arc};
+
+/// Severity of a log message, from most to least severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+        };
+        f.pad(name)
+    }
+}
+
+/// Destination for the diagnostic messages of the pool and the server.
+pub trait Logger: Send + Sync {
+    fn log(&self, level: Level, message: fmt::Arguments<'_>);
+}
+
+impl<T: Logger + ?Sized> Logger for Arc<T> {
+    fn log(&self, level: Level, message: fmt::Arguments<'_>) {
+        (**self).log(level, message)
+    }
+}
+
+/// Discards every message. This is the default logger.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Silent;
+
+impl Logger for Silent {
+    fn log(&self, _level: Level, _message: fmt::Arguments<'_>) {}
+}
+
+/// Writes messages at or above a level to standard error.
+#[derive(Debug, Clone, Copy)]
+pub struct StderrLogger {
+    level: Level,
+}
+
+impl StderrLogger {
+    pub fn new(level: Level) -> Self {
+        StderrLogger { level }
+    }
+}
+
+impl Logger for StderrLogger {
+    fn log(&self, level: Level, message: fmt::Arguments<'_>) {
+        if level <= self.level {
+            let _ = writeln!(std::io::stderr().lock(), "[{level:<5}] {message}");
+        }
+    }
+}
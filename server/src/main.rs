@@ -1,43 +1,162 @@
-use std::io::{BufReader, prelude::*};
-use std::net::{TcpListener, TcpStream};
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream};
 use std::fs;
+use std::path::Path;
+use std::process;
+use std::sync::Arc;
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+use server::config::{Config, ConfigError, USAGE};
+use server::http::{
+    serve_connection, AccessLog, CatchPanic, Compression, ConnectionConfig, ConnectionLimiter, Request, RequestId,
+    Response, Router, StaticFiles, StatusCode, Timing,
+};
+use server::log::{Level, StderrLogger};
+use server::signal;
 use server::ThreadPool;
 
+mod todos;
+
+/// How often the accept loop checks for a shutdown signal while no client connects.
+const ACCEPT_POLL: Duration = Duration::from_millis(100);
+/// How long the accept loop spends on turning away a client, at most.
+const REFUSE_TIMEOUT: Duration = Duration::from_secs(1);
+
 fn main() {
-    let listener = TcpListener::bind("127.0.0.1:7878").unwrap();
+    let config = match Config::load() {
+        Ok(config) => config,
+        Err(ConfigError::HelpRequested) => {
+            print!("{USAGE}");
+            return;
+        }
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            process::exit(2);
+        }
+    };
+
+    if let Err(e) = run(&config) {
+        eprintln!("Server error: {e}");
+        process::exit(1);
+    }
+}
+
+fn run(config: &Config) -> io::Result<()> {
+    signal::install_shutdown_handler()?;
+
+    let listener = TcpListener::bind(config.bind_address())?;
+    // Non-blocking, so the loop below notices a shutdown signal.
+    listener.set_nonblocking(true)?;
+
+    // Block the accept loop instead of queueing without limit when every worker is busy.
+    let pool = ThreadPool::builder(config.workers)
+        .queue_capacity(64)
+        .logger(StderrLogger::new(Level::Info))
+        .build()
+        .map_err(io::Error::other)?;
+
+    let access_log = match &config.access_log {
+        Some(path) => AccessLog::file(path)?,
+        None => AccessLog::stdout(),
+    }
+    .format(config.log_format);
+    let router = Arc::new(routes(&config.root, access_log.clone()));
+    let connection_config = Arc::new(
+        ConnectionConfig::new()
+            .header_timeout(config.header_timeout)
+            .body_timeout(config.body_timeout)
+            .write_timeout(config.write_timeout)
+            .max_body_size(config.max_body_size)
+            .access_log(access_log.clone()),
+    );
+    let limiter = ConnectionLimiter::new(config.max_connections_per_ip);
+
+    println!("Listening on http://{}", listener.local_addr()?);
 
-    let pool = ThreadPool::new(4);
+    while !signal::shutdown_requested() {
+        let (stream, peer) = match listener.accept() {
+            Ok(accepted) => accepted,
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                thread::sleep(ACCEPT_POLL);
+                continue;
+            }
+            Err(e) => {
+                // Such as running out of file descriptors: wait for some to be freed.
+                eprintln!("Failed to accept a connection: {e}");
+                thread::sleep(ACCEPT_POLL);
+                continue;
+            }
+        };
+        if let Err(e) = stream.set_nonblocking(false) {
+            eprintln!("Dropping connection: {e}");
+            continue;
+        }
+        let Some(permit) = limiter.try_acquire(peer.ip()) else {
+            refuse(&stream, peer, &access_log);
+            continue;
+        };
 
-    for stream in listener.incoming().take(2) {
-        let stream = stream.unwrap();
+        let router = Arc::clone(&router);
+        let connection_config = Arc::clone(&connection_config);
 
-        pool.execute(|| {
-            handle_connect(stream);
-        });
+        if let Err(e) = pool.execute(move || {
+            let _permit = permit;
+            match serve_connection(&stream, &router, &connection_config) {
+                Err(e) if !e.is_disconnect() => eprintln!("Connection failed: {e}"),
+                _ => {}
+            }
+        }) {
+            eprintln!("Dropping connection: {e}");
+        }
     }
 
     println!("Shutting down.");
-}
+    drop(listener);
 
-fn handle_connect(mut stream: TcpStream) {
-    let buf_reader = BufReader::new(&stream);
+    let report = pool.shutdown(config.shutdown_timeout);
+    if !report.is_complete() {
+        eprintln!("Gave up on {} open connection(s) after {:?}.", report.unfinished.len(), config.shutdown_timeout);
+    }
+    Ok(())
+}
 
-    let request_line = buf_reader.lines().next().unwrap().unwrap();
+/// Turns away a client that has too many connections open, from the accept
+/// loop rather than a worker. The response is small enough not to block.
+fn refuse(stream: &TcpStream, peer: SocketAddr, access_log: &AccessLog) {
+    let started = Instant::now();
+    let _ = stream.set_write_timeout(Some(REFUSE_TIMEOUT));
+    let response = Response::error(StatusCode::TooManyRequests).header("Connection", "close");
+    access_log.log_refused(Some(peer), None, &response, started);
+    let _ = response.write_to(&mut &*stream, false);
+}
 
-    let (status_line, filename) = match &request_line[..] {
-        "GET / HTTP/1.1" => ("HTTP/1.1 200 OK", "hello.html"),
-        "GET /sleep HTTP/1.1" => {
+fn routes(root: &Path, access_log: AccessLog) -> Router {
+    let mut router = Router::new();
+    todos::register(&mut router);
+    router
+        .get("/", |_: &Request| page(StatusCode::Ok, "hello.html"))
+        .get("/sleep", |_: &Request| {
             thread::sleep(Duration::from_secs(5));
-            ("HTTP/1.1 200 OK", "hello.html")
-        },
-        _ => ("HTTP/1.1 404 NOT FOUND", "404.html"),
-    };
-
-    let contents = fs::read_to_string(filename).unwrap();
-    let length = contents.len();
-    let response = format!("{status_line}\r\nContent-Length: {length}\r\n\r\n{contents}");
+            page(StatusCode::Ok, "hello.html")
+        })
+        .get("/static/*", StaticFiles::new(root))
+        .not_found(|_: &Request| page(StatusCode::NotFound, "404.html"))
+        .wrap(access_log)
+        .wrap(CatchPanic)
+        .wrap(RequestId::new())
+        .wrap(Timing)
+        .wrap(Compression::new());
+    router
+}
 
-    stream.write_all(response.as_bytes()).unwrap();
+/// Serves the page in `filename`. If it cannot be read, the client gets a
+/// plain `status` response instead, or a 500 one for a successful `status`.
+fn page(status: StatusCode, filename: &str) -> Response {
+    match fs::read_to_string(filename) {
+        Ok(contents) => Response::html(status, contents),
+        Err(e) => {
+            eprintln!("Failed to read {filename}: {e}");
+            Response::error(if status.is_success() { StatusCode::InternalServerError } else { status })
+        }
+    }
 }
@@ -0,0 +1,651 @@
+use std::{
+    any::Any,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+mod builder;
+mod events;
+mod parallel;
+mod priority;
+mod queue;
+mod scope;
+mod shutdown;
+mod timer;
+mod worker;
+
+pub use builder::{Builder, PoolCreationError};
+pub use events::{MetricsSnapshot, PoolEvent, PoolMetrics, PoolObserver, WorkerStats};
+pub use priority::Priority;
+pub use queue::{ExecuteError, RejectionPolicy};
+pub use scope::Scope;
+pub use shutdown::ShutdownReport;
+pub use timer::ScheduledHandle;
+
+use timer::Timer;
+use worker::Shared;
+
+/// A submitted closure, its priority and, if the pool has an observer, the
+/// time it entered the queue.
+struct Job {
+    run: Box<dyn FnOnce() + Send + 'static>,
+    priority: Priority,
+    queued_at: Option<Instant>,
+}
+
+type PanicHandler = dyn Fn(usize, &(dyn Any + Send)) + Send + Sync;
+
+pub struct ThreadPool {
+    shared: Arc<Shared>,
+    timer: Mutex<Option<Timer>>
+}
+
+impl ThreadPool {
+    /// Creates a pool with `size` workers.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `size` is zero or a worker thread cannot be spawned.
+    /// Use [`ThreadPool::build`] to handle those cases instead.
+    pub fn new(size: usize) -> Self {
+        Self::build(size).unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Creates a pool with `size` workers, returning an error instead of panicking.
+    pub fn build(size: usize) -> Result<Self, PoolCreationError> {
+        Builder::new(size).build()
+    }
+
+    /// Returns a [`Builder`] for configuring thread names, stack size, the
+    /// job queue and elastic sizing.
+    pub fn builder(size: usize) -> Builder {
+        Builder::new(size)
+    }
+
+    /// Creates a pool whose workers call `handler` with their id and the
+    /// panic payload whenever a job panics. The worker keeps running afterwards.
+    pub fn with_panic_handler<H>(size: usize, handler: H) -> Self
+    where H: Fn(usize, &(dyn Any + Send)) + Send + Sync + 'static {
+        Builder::new(size).panic_handler(handler).build().unwrap_or_else(|err| panic!("{err}"))
+    }
+
+    /// Queues `f` to run on a worker.
+    ///
+    /// When the queue is bounded and full, the pool's [`RejectionPolicy`]
+    /// decides whether this call blocks, fails, evicts the oldest job or
+    /// runs `f` on the calling thread.
+    pub fn execute<F>(&self, f: F) -> Result<(), ExecuteError>
+    where F: FnOnce() + Send + 'static {
+        self.execute_with_priority(Priority::Normal, f)
+    }
+
+    /// Like [`execute`](Self::execute), but jobs of higher `priority` are
+    /// taken off the queue first.
+    ///
+    /// Lower priorities are not starved: once a waiting level has been
+    /// passed over a few times in a row, its oldest job goes next.
+    pub fn execute_with_priority<F>(&self, priority: Priority, f: F) -> Result<(), ExecuteError>
+    where F: FnOnce() + Send + 'static {
+        self.shared.submit(Box::new(f), priority)
+    }
+
+    /// Returns the number of jobs waiting for a worker.
+    pub fn queue_depth(&self) -> usize {
+        self.shared.queue.len()
+    }
+
+    /// Returns the number of workers currently running.
+    pub fn size(&self) -> usize {
+        self.shared.size()
+    }
+
+    /// Stops accepting new jobs, lets the workers drain the queue and waits
+    /// up to `timeout` for them to exit.
+    ///
+    /// Later calls to [`execute`](Self::execute) fail with [`ExecuteError::ShutDown`].
+    pub fn shutdown(&self, timeout: Duration) -> ShutdownReport {
+        self.stop_timer();
+        self.shared.queue.close();
+        let unfinished = self.shared.join_workers(Some(Instant::now() + timeout));
+
+        ShutdownReport { discarded_jobs: 0, unfinished }
+    }
+
+    /// Like [`shutdown`](Self::shutdown), but discards every job that has not started yet.
+    pub fn shutdown_now(&self, timeout: Duration) -> ShutdownReport {
+        self.stop_timer();
+        let discarded_jobs = self.shared.queue.close_and_clear();
+        let unfinished = self.shared.join_workers(Some(Instant::now() + timeout));
+
+        ShutdownReport { discarded_jobs, unfinished }
+    }
+
+    fn spawn(config: Builder) -> Result<Self, PoolCreationError> {
+        let (min, max) = (config.size, config.max_workers());
+
+        if max == 0 {
+            return Err(PoolCreationError::ZeroSize);
+        }
+        if max < min {
+            return Err(PoolCreationError::MaxBelowMin { min, max });
+        }
+
+        // Dropping the pool on error joins the workers that did start.
+        let pool = ThreadPool { shared: Arc::new(Shared::new(config)), timer: Mutex::new(None) };
+
+        for id in 0..min {
+            pool.shared.spawn_worker().map_err(|source| PoolCreationError::Spawn { id, source })?;
+        }
+
+        Ok(pool)
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.stop_timer();
+        self.shared.queue.close();
+
+        self.shared.join_workers(None);
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Extracts the message from a panic payload, as produced by `panic!`.
+pub fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        s
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s
+    } else {
+        "Box<dyn Any>"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::panic::{self, AssertUnwindSafe};
+    use std::sync::{atomic::{AtomicUsize, Ordering}, mpsc};
+    use std::thread;
+
+    #[test]
+    fn panicking_jobs_do_not_shrink_the_pool() {
+        let panics = Arc::new(AtomicUsize::new(0));
+        let done = Arc::new(AtomicUsize::new(0));
+
+        {
+            let counter = Arc::clone(&panics);
+            let pool = ThreadPool::with_panic_handler(2, move |_, payload| {
+                assert_eq!(panic_message(payload), "boom");
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+
+            for _ in 0..4 {
+                pool.execute(|| panic!("boom")).unwrap();
+            }
+            for _ in 0..8 {
+                let done = Arc::clone(&done);
+                pool.execute(move || {
+                    done.fetch_add(1, Ordering::SeqCst);
+                }).unwrap();
+            }
+        }
+
+        assert_eq!(panics.load(Ordering::SeqCst), 4);
+        assert_eq!(done.load(Ordering::SeqCst), 8);
+    }
+
+    #[test]
+    fn build_rejects_zero_workers() {
+        assert!(matches!(ThreadPool::build(0), Err(PoolCreationError::ZeroSize)));
+    }
+
+    #[test]
+    fn workers_are_named_after_the_prefix() {
+        let pool = ThreadPool::builder(1).thread_name("http").build().unwrap();
+        let (tx, rx) = mpsc::channel();
+
+        pool.execute(move || {
+            tx.send(thread::current().name().map(String::from)).unwrap();
+        }).unwrap();
+
+        assert_eq!(rx.recv().unwrap().as_deref(), Some("http-0"));
+    }
+
+    // Occupies the single worker of `pool` until the returned sender is dropped.
+    fn block_worker(pool: &ThreadPool) -> mpsc::Sender<()> {
+        let (started_tx, started_rx) = mpsc::channel();
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            let _ = release_rx.recv();
+        }).unwrap();
+        started_rx.recv().unwrap();
+
+        release_tx
+    }
+
+    fn bounded(policy: RejectionPolicy) -> ThreadPool {
+        ThreadPool::builder(1).queue_capacity(2).rejection_policy(policy).build().unwrap()
+    }
+
+    #[test]
+    fn reject_policy_fails_when_full() {
+        let pool = bounded(RejectionPolicy::Reject);
+        let release = block_worker(&pool);
+
+        pool.execute(|| {}).unwrap();
+        pool.execute(|| {}).unwrap();
+        assert_eq!(pool.queue_depth(), 2);
+        assert_eq!(pool.execute(|| {}), Err(ExecuteError::Rejected));
+
+        drop(release);
+    }
+
+    #[test]
+    fn drop_oldest_policy_evicts_the_head_of_the_queue() {
+        let pool = bounded(RejectionPolicy::DropOldest);
+        let release = block_worker(&pool);
+        let (tx, rx) = mpsc::channel();
+
+        for i in 0..4 {
+            let tx = tx.clone();
+            pool.execute(move || tx.send(i).unwrap()).unwrap();
+        }
+        assert_eq!(pool.queue_depth(), 2);
+
+        drop(release);
+        drop(tx);
+        drop(pool);
+        assert_eq!(rx.iter().collect::<Vec<_>>(), vec![2, 3]);
+    }
+
+    #[test]
+    fn caller_runs_policy_executes_on_the_submitting_thread() {
+        let pool = bounded(RejectionPolicy::CallerRuns);
+        let release = block_worker(&pool);
+        let caller = thread::current().id();
+        let (tx, rx) = mpsc::channel();
+
+        for _ in 0..3 {
+            let tx = tx.clone();
+            pool.execute(move || tx.send(thread::current().id() == caller).unwrap()).unwrap();
+        }
+
+        assert!(rx.recv().unwrap());
+        drop(release);
+    }
+
+    #[test]
+    fn block_policy_waits_for_a_free_slot() {
+        let pool = bounded(RejectionPolicy::Block);
+        let release = block_worker(&pool);
+
+        pool.execute(|| {}).unwrap();
+        pool.execute(|| {}).unwrap();
+
+        let releaser = thread::spawn(move || {
+            thread::sleep(std::time::Duration::from_millis(50));
+            drop(release);
+        });
+        pool.execute(|| {}).unwrap();
+
+        releaser.join().unwrap();
+    }
+
+    #[test]
+    fn shutdown_drains_queued_jobs() {
+        let pool = ThreadPool::new(2);
+        let done = Arc::new(AtomicUsize::new(0));
+
+        for _ in 0..10 {
+            let done = Arc::clone(&done);
+            pool.execute(move || {
+                thread::sleep(Duration::from_millis(5));
+                done.fetch_add(1, Ordering::SeqCst);
+            }).unwrap();
+        }
+
+        let report = pool.shutdown(Duration::from_secs(5));
+
+        assert!(report.is_complete());
+        assert_eq!(done.load(Ordering::SeqCst), 10);
+        assert_eq!(pool.execute(|| {}), Err(ExecuteError::ShutDown));
+    }
+
+    #[test]
+    fn shutdown_reports_stuck_workers() {
+        let pool = ThreadPool::new(2);
+        let (release, stuck) = mpsc::channel::<()>();
+
+        pool.execute(move || {
+            let _ = stuck.recv();
+        }).unwrap();
+        thread::sleep(Duration::from_millis(20));
+
+        let report = pool.shutdown(Duration::from_millis(50));
+
+        assert_eq!(report.unfinished.len(), 1);
+        drop(release);
+    }
+
+    #[test]
+    fn shutdown_now_discards_queued_jobs() {
+        let pool = ThreadPool::new(1);
+        let (started_tx, started_rx) = mpsc::channel();
+
+        pool.execute(move || {
+            started_tx.send(()).unwrap();
+            thread::sleep(Duration::from_millis(50));
+        }).unwrap();
+        started_rx.recv().unwrap();
+        for _ in 0..3 {
+            pool.execute(|| panic!("discarded jobs must not run")).unwrap();
+        }
+
+        let report = pool.shutdown_now(Duration::from_secs(5));
+
+        assert_eq!(report, ShutdownReport { discarded_jobs: 3, unfinished: Vec::new() });
+    }
+
+    #[test]
+    fn jobs_submitted_from_a_worker_are_stolen_by_idle_workers() {
+        let pool = Arc::new(ThreadPool::new(4));
+        let (tx, rx) = mpsc::channel();
+
+        let spawner = Arc::clone(&pool);
+        pool.execute(move || {
+            for _ in 0..64 {
+                let tx = tx.clone();
+                spawner.execute(move || {
+                    thread::sleep(Duration::from_millis(2));
+                    tx.send(thread::current().id()).unwrap();
+                }).unwrap();
+            }
+        }).unwrap();
+
+        let threads: HashSet<_> = rx.iter().take(64).collect();
+        assert!(threads.len() > 1);
+    }
+
+    #[test]
+    fn build_rejects_max_below_min() {
+        let result = ThreadPool::builder(4).max_size(2).build();
+        assert!(matches!(result, Err(PoolCreationError::MaxBelowMin { min: 4, max: 2 })));
+    }
+
+    #[test]
+    fn elastic_pool_grows_under_load_and_shrinks_when_idle() {
+        let pool = ThreadPool::builder(1)
+            .max_size(4)
+            .keep_alive(Duration::from_millis(50))
+            .build()
+            .unwrap();
+        assert_eq!(pool.size(), 1);
+
+        let (release_tx, release_rx) = mpsc::channel::<()>();
+        let release_rx = Arc::new(Mutex::new(release_rx));
+        for _ in 0..4 {
+            let release_rx = Arc::clone(&release_rx);
+            pool.execute(move || {
+                let _ = release_rx.lock().unwrap().recv();
+            }).unwrap();
+        }
+        assert_eq!(pool.size(), 4);
+
+        drop(release_tx);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while pool.size() > 1 && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(pool.size(), 1);
+    }
+
+    #[test]
+    fn metrics_count_every_job() {
+        let metrics = Arc::new(PoolMetrics::new());
+        let pool = ThreadPool::builder(2).observer(Arc::clone(&metrics)).build().unwrap();
+
+        for i in 0..10 {
+            pool.execute(move || {
+                if i % 5 == 0 {
+                    panic!("job {i} failed");
+                }
+            }).unwrap();
+        }
+        drop(pool);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.submitted, 10);
+        assert_eq!(snapshot.started, 10);
+        assert_eq!(snapshot.completed, 8);
+        assert_eq!(snapshot.panicked, 2);
+        assert_eq!(snapshot.workers.values().map(|w| w.jobs).sum::<u64>(), 10);
+    }
+
+    #[test]
+    fn scoped_jobs_borrow_from_the_caller() {
+        let pool = ThreadPool::new(3);
+        let words = [String::from("a"), String::from("bb"), String::from("ccc")];
+        let mut lengths = vec![0; words.len()];
+
+        pool.scope(|s| {
+            for (word, length) in words.iter().zip(lengths.iter_mut()) {
+                s.spawn(move || *length = word.len()).unwrap();
+            }
+        });
+
+        assert_eq!(lengths, vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn scope_inside_a_worker_does_not_deadlock() {
+        let pool = Arc::new(ThreadPool::new(1));
+        let (tx, rx) = mpsc::channel();
+
+        let inner = Arc::clone(&pool);
+        pool.execute(move || {
+            let mut sum = 0;
+            inner.scope(|s| {
+                s.spawn(|| sum = (1..=10).sum()).unwrap();
+            });
+            tx.send(sum).unwrap();
+        }).unwrap();
+
+        assert_eq!(rx.recv_timeout(Duration::from_secs(5)), Ok(55));
+    }
+
+    #[test]
+    fn scope_resumes_a_scoped_panic_after_all_jobs_finish() {
+        let pool = ThreadPool::new(2);
+        let finished = AtomicUsize::new(0);
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            pool.scope(|s| {
+                s.spawn(|| panic!("scoped failure")).unwrap();
+                s.spawn(|| {
+                    thread::sleep(Duration::from_millis(20));
+                    finished.fetch_add(1, Ordering::SeqCst);
+                }).unwrap();
+            })
+        }));
+
+        let payload = result.unwrap_err();
+        assert_eq!(panic_message(&*payload), "scoped failure");
+        assert_eq!(finished.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn schedule_after_runs_once_the_delay_has_passed() {
+        let pool = ThreadPool::new(1);
+        let (tx, rx) = mpsc::channel();
+        let start = Instant::now();
+
+        pool.schedule_after(Duration::from_millis(50), move || tx.send(Instant::now()).unwrap()).unwrap();
+
+        let ran_at = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(ran_at - start >= Duration::from_millis(50));
+    }
+
+    #[test]
+    fn cancelled_jobs_do_not_run() {
+        let pool = ThreadPool::new(1);
+        let (tx, rx) = mpsc::channel::<()>();
+
+        let handle = pool.schedule_after(Duration::from_millis(30), move || tx.send(()).unwrap()).unwrap();
+        handle.cancel();
+
+        assert!(rx.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+
+    #[test]
+    fn fixed_rate_jobs_repeat_until_cancelled() {
+        let pool = ThreadPool::new(2);
+        let ticks = Arc::new(AtomicUsize::new(0));
+
+        let counter = Arc::clone(&ticks);
+        let handle = pool.schedule_at_fixed_rate(Duration::from_millis(10), move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        }).unwrap();
+
+        thread::sleep(Duration::from_millis(100));
+        handle.cancel();
+        thread::sleep(Duration::from_millis(30));
+        let after_cancel = ticks.load(Ordering::SeqCst);
+        assert!(after_cancel >= 3, "only {after_cancel} ticks");
+
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(ticks.load(Ordering::SeqCst), after_cancel);
+    }
+
+    #[test]
+    fn scheduling_after_shutdown_fails() {
+        let pool = ThreadPool::new(1);
+        pool.shutdown(Duration::from_secs(1));
+
+        let result = pool.schedule_after(Duration::ZERO, || {});
+        assert!(matches!(result, Err(ExecuteError::ShutDown)));
+    }
+
+    #[test]
+    fn higher_priority_jobs_run_first() {
+        let pool = ThreadPool::new(1);
+        let release = block_worker(&pool);
+        let (tx, rx) = mpsc::channel();
+
+        for priority in [Priority::Low, Priority::Normal, Priority::High] {
+            let tx = tx.clone();
+            pool.execute_with_priority(priority, move || tx.send(priority).unwrap()).unwrap();
+        }
+        drop(release);
+
+        let order: Vec<_> = rx.iter().take(3).collect();
+        assert_eq!(order, [Priority::High, Priority::Normal, Priority::Low]);
+    }
+
+    #[test]
+    fn low_priority_jobs_are_not_starved() {
+        let pool = ThreadPool::new(1);
+        let release = block_worker(&pool);
+        let (tx, rx) = mpsc::channel();
+
+        let low = tx.clone();
+        pool.execute_with_priority(Priority::Low, move || low.send(Priority::Low).unwrap()).unwrap();
+        for _ in 0..32 {
+            let tx = tx.clone();
+            pool.execute_with_priority(Priority::High, move || tx.send(Priority::High).unwrap()).unwrap();
+        }
+        drop(release);
+
+        let order: Vec<_> = rx.iter().take(33).collect();
+        let position = order.iter().position(|&p| p == Priority::Low).unwrap();
+        assert!(position <= priority::STARVATION_LIMIT, "low priority job ran {position}th");
+    }
+
+    #[test]
+    fn low_priority_jobs_are_not_starved_by_jobs_from_workers() {
+        // Each run submits the next from inside the pool, into the worker's own deque.
+        fn resubmit(pool: Arc<ThreadPool>, tx: mpsc::Sender<Priority>, left: usize) {
+            tx.send(Priority::Normal).unwrap();
+            if left > 0 {
+                let spawner = Arc::clone(&pool);
+                pool.execute(move || resubmit(spawner, tx, left - 1)).unwrap();
+            }
+        }
+
+        let pool = Arc::new(ThreadPool::new(1));
+        let release = block_worker(&pool);
+        let (tx, rx) = mpsc::channel();
+
+        let (spawner, normal) = (Arc::clone(&pool), tx.clone());
+        pool.execute(move || resubmit(spawner, normal, 63)).unwrap();
+        pool.execute_with_priority(Priority::Low, move || tx.send(Priority::Low).unwrap()).unwrap();
+        drop(release);
+
+        let order: Vec<_> = rx.iter().take(65).collect();
+        let position = order.iter().position(|&p| p == Priority::Low).unwrap();
+        assert!(position <= priority::STARVATION_LIMIT + 1, "low priority job ran {position}th");
+    }
+
+    #[test]
+    fn par_map_keeps_input_order() {
+        let pool = ThreadPool::new(4);
+        let input: Vec<u32> = (0..1000).collect();
+
+        let doubled = pool.par_map(&input, |n| n * 2).unwrap();
+
+        assert_eq!(doubled, input.iter().map(|n| n * 2).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn par_for_each_visits_every_item() {
+        let pool = ThreadPool::new(4);
+        let sum = AtomicUsize::new(0);
+
+        pool.par_for_each(1..=100, |n| {
+            sum.fetch_add(n, Ordering::SeqCst);
+        }).unwrap();
+
+        assert_eq!(sum.into_inner(), 5050);
+    }
+
+    #[test]
+    fn par_reduce_combines_neighbours_in_order() {
+        let pool = ThreadPool::new(4);
+        let words = (0..50).map(|n| n.to_string());
+
+        let joined = pool.par_reduce(words, |a, b| a + &b).unwrap();
+
+        assert_eq!(joined, Some((0..50).map(|n| n.to_string()).collect::<String>()));
+        assert_eq!(pool.par_reduce(Vec::<u32>::new(), |a, b| a + b), Ok(None));
+    }
+
+    #[test]
+    fn parallel_calls_fail_when_a_chunk_is_dropped() {
+        let metrics = Arc::new(PoolMetrics::new());
+        let pool = ThreadPool::builder(1)
+            .queue_capacity(2)
+            .rejection_policy(RejectionPolicy::DropOldest)
+            .observer(Arc::clone(&metrics))
+            .build()
+            .unwrap();
+        let release = block_worker(&pool);
+
+        thread::scope(|s| {
+            // One worker makes four chunks, and only two fit in the queue.
+            let mapped = s.spawn(|| pool.par_map(0..8, |n| n * 2));
+            while metrics.snapshot().submitted < 5 {
+                thread::yield_now();
+            }
+            drop(release);
+
+            assert_eq!(mapped.join().unwrap(), Err(ExecuteError::Rejected));
+        });
+    }
+}
@@ -0,0 +1,160 @@
+use std::{any::Any, error::Error, fmt, io, sync::Arc, thread, time::Duration};
+
+use crate::log::{Logger, Silent};
+
+use super::{PanicHandler, PoolObserver, RejectionPolicy, ThreadPool};
+
+/// Error returned when a [`ThreadPool`] cannot be created.
+#[derive(Debug)]
+pub enum PoolCreationError {
+    /// The pool was asked for zero workers.
+    ZeroSize,
+    /// The maximum size is smaller than the minimum size.
+    MaxBelowMin { min: usize, max: usize },
+    /// The operating system refused to spawn worker `id`.
+    Spawn { id: usize, source: io::Error },
+}
+
+impl fmt::Display for PoolCreationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolCreationError::ZeroSize => write!(f, "thread pool size must be greater than zero"),
+            PoolCreationError::MaxBelowMin { min, max } => {
+                write!(f, "thread pool maximum size {max} is smaller than its minimum size {min}")
+            }
+            PoolCreationError::Spawn { id, source } => write!(f, "failed to spawn worker {id}: {source}"),
+        }
+    }
+}
+
+impl Error for PoolCreationError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            PoolCreationError::ZeroSize | PoolCreationError::MaxBelowMin { .. } => None,
+            PoolCreationError::Spawn { source, .. } => Some(source),
+        }
+    }
+}
+
+/// Configures and creates a [`ThreadPool`].
+///
+/// ```
+/// use server::ThreadPool;
+///
+/// let pool = ThreadPool::builder(4)
+///     .thread_name("http")
+///     .stack_size(256 * 1024)
+///     .queue_capacity(64)
+///     .max_size(16)
+///     .build()
+///     .unwrap();
+///
+/// pool.execute(|| println!("running on {:?}", std::thread::current().name())).unwrap();
+/// ```
+pub struct Builder {
+    pub(super) size: usize,
+    max_size: Option<usize>,
+    pub(super) keep_alive: Duration,
+    pub(super) name_prefix: String,
+    stack_size: Option<usize>,
+    pub(super) panic_handler: Option<Arc<PanicHandler>>,
+    pub(super) observer: Option<Arc<dyn PoolObserver>>,
+    pub(super) logger: Arc<dyn Logger>,
+    pub(super) queue_capacity: Option<usize>,
+    pub(super) rejection_policy: RejectionPolicy,
+}
+
+impl Builder {
+    /// Starts configuring a pool that keeps `size` workers alive.
+    pub fn new(size: usize) -> Self {
+        Builder {
+            size,
+            max_size: None,
+            keep_alive: Duration::from_secs(60),
+            name_prefix: String::from("worker"),
+            stack_size: None,
+            panic_handler: None,
+            observer: None,
+            logger: Arc::new(Silent),
+            queue_capacity: None,
+            rejection_policy: RejectionPolicy::default(),
+        }
+    }
+
+    /// Lets the pool grow to `max` workers while jobs are waiting. The extra
+    /// workers exit after being idle for the keep-alive time. Defaults to the
+    /// pool's `size`, which gives a fixed-size pool.
+    pub fn max_size(mut self, max: usize) -> Self {
+        self.max_size = Some(max);
+        self
+    }
+
+    /// Sets how long a worker above the minimum size waits for a job before
+    /// exiting. Defaults to 60 seconds.
+    pub fn keep_alive(mut self, keep_alive: Duration) -> Self {
+        self.keep_alive = keep_alive;
+        self
+    }
+
+    /// Names worker threads `{prefix}-{id}`. Defaults to `worker`.
+    pub fn thread_name(mut self, prefix: impl Into<String>) -> Self {
+        self.name_prefix = prefix.into();
+        self
+    }
+
+    /// Sets the stack size of each worker thread in bytes.
+    pub fn stack_size(mut self, bytes: usize) -> Self {
+        self.stack_size = Some(bytes);
+        self
+    }
+
+    /// Calls `handler` with the worker id and panic payload whenever a job panics.
+    pub fn panic_handler<H>(mut self, handler: H) -> Self
+    where H: Fn(usize, &(dyn Any + Send)) + Send + Sync + 'static {
+        self.panic_handler = Some(Arc::new(handler));
+        self
+    }
+
+    /// Reports job submissions, starts, completions and panics to `observer`,
+    /// for example a [`PoolMetrics`](super::PoolMetrics).
+    pub fn observer(mut self, observer: impl PoolObserver + 'static) -> Self {
+        self.observer = Some(Arc::new(observer));
+        self
+    }
+
+    /// Sends the pool's diagnostic messages to `logger`. The pool is silent by default.
+    pub fn logger(mut self, logger: impl Logger + 'static) -> Self {
+        self.logger = Arc::new(logger);
+        self
+    }
+
+    /// Bounds the job queue to `capacity` waiting jobs (at least one).
+    /// The queue is unbounded by default.
+    pub fn queue_capacity(mut self, capacity: usize) -> Self {
+        self.queue_capacity = Some(capacity.max(1));
+        self
+    }
+
+    /// Sets what happens when a job is submitted to a full queue. Defaults to [`RejectionPolicy::Block`].
+    pub fn rejection_policy(mut self, policy: RejectionPolicy) -> Self {
+        self.rejection_policy = policy;
+        self
+    }
+
+    pub fn build(self) -> Result<ThreadPool, PoolCreationError> {
+        ThreadPool::spawn(self)
+    }
+
+    pub(super) fn max_workers(&self) -> usize {
+        self.max_size.unwrap_or(self.size)
+    }
+
+    pub(super) fn thread_builder(&self, id: usize) -> thread::Builder {
+        let builder = thread::Builder::new().name(format!("{}-{id}", self.name_prefix));
+
+        match self.stack_size {
+            Some(bytes) => builder.stack_size(bytes),
+            None => builder,
+        }
+    }
+}
@@ -0,0 +1,137 @@
+use std::{
+    any::Any,
+    collections::BTreeMap,
+    sync::{atomic::{AtomicU64, Ordering::Relaxed}, Arc, Mutex},
+    time::Duration,
+};
+
+use super::lock;
+
+/// Something that happened to a job in a [`ThreadPool`](super::ThreadPool).
+#[derive(Debug)]
+pub enum PoolEvent<'a> {
+    /// `execute` accepted a job.
+    Submitted,
+    /// A worker took a job off the queue after it waited `queued_for`.
+    Started { worker: usize, queued_for: Duration },
+    /// A job returned normally after running for `ran_for`.
+    Completed { worker: usize, ran_for: Duration },
+    /// A job panicked after running for `ran_for`.
+    Panicked { worker: usize, ran_for: Duration, payload: &'a (dyn Any + Send) },
+}
+
+/// Receives every [`PoolEvent`]. Called on the thread where the event
+/// happens, so implementations should be quick.
+pub trait PoolObserver: Send + Sync {
+    fn on_event(&self, event: &PoolEvent<'_>);
+}
+
+impl<T: PoolObserver + ?Sized> PoolObserver for Arc<T> {
+    fn on_event(&self, event: &PoolEvent<'_>) {
+        (**self).on_event(event)
+    }
+}
+
+/// Totals for a single worker, as reported by [`PoolMetrics::snapshot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct WorkerStats {
+    pub jobs: u64,
+    pub panicked: u64,
+    pub busy: Duration,
+}
+
+/// A point-in-time copy of [`PoolMetrics`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct MetricsSnapshot {
+    pub submitted: u64,
+    pub started: u64,
+    pub completed: u64,
+    pub panicked: u64,
+    /// Time jobs spent in the queue, summed over every started job.
+    pub queue_wait: Duration,
+    pub workers: BTreeMap<usize, WorkerStats>,
+}
+
+impl MetricsSnapshot {
+    /// Average time a started job spent waiting in the queue.
+    pub fn mean_queue_wait(&self) -> Duration {
+        match u32::try_from(self.started) {
+            Ok(0) => Duration::ZERO,
+            Ok(started) => self.queue_wait / started,
+            Err(_) => Duration::from_secs_f64(self.queue_wait.as_secs_f64() / self.started as f64),
+        }
+    }
+}
+
+/// A [`PoolObserver`] that keeps counters and timings.
+///
+/// ```
+/// use std::sync::Arc;
+/// use server::{PoolMetrics, ThreadPool};
+///
+/// let metrics = Arc::new(PoolMetrics::new());
+/// let pool = ThreadPool::builder(2).observer(Arc::clone(&metrics)).build().unwrap();
+///
+/// pool.execute(|| {}).unwrap();
+/// drop(pool);
+///
+/// assert_eq!(metrics.snapshot().completed, 1);
+/// ```
+#[derive(Debug, Default)]
+pub struct PoolMetrics {
+    submitted: AtomicU64,
+    started: AtomicU64,
+    completed: AtomicU64,
+    panicked: AtomicU64,
+    queue_wait_nanos: AtomicU64,
+    workers: Mutex<BTreeMap<usize, WorkerStats>>,
+}
+
+impl PoolMetrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn snapshot(&self) -> MetricsSnapshot {
+        MetricsSnapshot {
+            submitted: self.submitted.load(Relaxed),
+            started: self.started.load(Relaxed),
+            completed: self.completed.load(Relaxed),
+            panicked: self.panicked.load(Relaxed),
+            queue_wait: Duration::from_nanos(self.queue_wait_nanos.load(Relaxed)),
+            workers: lock(&self.workers).clone(),
+        }
+    }
+
+    fn record_run(&self, worker: usize, ran_for: Duration, panicked: bool) {
+        let mut workers = lock(&self.workers);
+        let stats = workers.entry(worker).or_default();
+
+        stats.jobs += 1;
+        stats.panicked += u64::from(panicked);
+        stats.busy += ran_for;
+    }
+}
+
+impl PoolObserver for PoolMetrics {
+    fn on_event(&self, event: &PoolEvent<'_>) {
+        match *event {
+            PoolEvent::Submitted => {
+                self.submitted.fetch_add(1, Relaxed);
+            }
+            PoolEvent::Started { queued_for, .. } => {
+                self.started.fetch_add(1, Relaxed);
+                let nanos = u64::try_from(queued_for.as_nanos()).unwrap_or(u64::MAX);
+                self.queue_wait_nanos.fetch_add(nanos, Relaxed);
+            }
+            PoolEvent::Completed { worker, ran_for } => {
+                self.completed.fetch_add(1, Relaxed);
+                self.record_run(worker, ran_for, false);
+            }
+            PoolEvent::Panicked { worker, ran_for, .. } => {
+                self.panicked.fetch_add(1, Relaxed);
+                self.record_run(worker, ran_for, true);
+            }
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use super::{ExecuteError, ThreadPool};
+#[cfg(doc)]
+use super::RejectionPolicy;
+
+/// How many chunks each worker gets, so that uneven chunks still balance out.
+const CHUNKS_PER_WORKER: usize = 4;
+
+impl ThreadPool {
+    /// Applies `f` to every item on the pool's workers and returns the
+    /// results in input order.
+    ///
+    /// The items are split into a few chunks per worker, and each chunk runs
+    /// as one job. Like [`scope`](Self::scope), this returns once every chunk
+    /// has finished and resumes the panic of a chunk that panicked.
+    ///
+    /// Fails if a chunk is not accepted by the pool, or if it is discarded
+    /// before it runs, by [`RejectionPolicy::DropOldest`] or a shutdown.
+    ///
+    /// ```
+    /// use server::ThreadPool;
+    ///
+    /// let pool = ThreadPool::new(4);
+    /// let squares = pool.par_map(1..=5, |n: u64| n * n).unwrap();
+    ///
+    /// assert_eq!(squares, [1, 4, 9, 16, 25]);
+    /// ```
+    pub fn par_map<I, R, F>(&self, items: I, f: F) -> Result<Vec<R>, ExecuteError>
+    where
+        I: IntoIterator,
+        I::Item: Send,
+        R: Send,
+        F: Fn(I::Item) -> R + Sync,
+    {
+        let chunks = self.run_chunks(items, |chunk| chunk.into_iter().map(&f).collect::<Vec<_>>())?;
+        Ok(chunks.into_iter().flatten().collect())
+    }
+
+    /// Calls `f` on every item on the pool's workers, in no particular order.
+    pub fn par_for_each<I, F>(&self, items: I, f: F) -> Result<(), ExecuteError>
+    where
+        I: IntoIterator,
+        I::Item: Send,
+        F: Fn(I::Item) + Sync,
+    {
+        self.run_chunks(items, |chunk| chunk.into_iter().for_each(&f)).map(drop)
+    }
+
+    /// Combines all items with `op` on the pool's workers. Returns `None` if
+    /// there are no items.
+    ///
+    /// `op` must be associative, but need not be commutative: items are only
+    /// ever combined with their neighbours, in input order.
+    pub fn par_reduce<I, F>(&self, items: I, op: F) -> Result<Option<I::Item>, ExecuteError>
+    where
+        I: IntoIterator,
+        I::Item: Send,
+        F: Fn(I::Item, I::Item) -> I::Item + Sync,
+    {
+        let partials = self.run_chunks(items, |chunk| chunk.into_iter().reduce(&op))?;
+        Ok(partials.into_iter().flatten().reduce(&op))
+    }
+
+    /// Runs `f` on consecutive chunks of `items` as scoped jobs and returns
+    /// its results in chunk order.
+    fn run_chunks<I, R, F>(&self, items: I, f: F) -> Result<Vec<R>, ExecuteError>
+    where
+        I: IntoIterator,
+        I::Item: Send,
+        R: Send,
+        F: Fn(Vec<I::Item>) -> R + Sync,
+    {
+        let items: Vec<_> = items.into_iter().collect();
+        let chunk_size = items.len().div_ceil(self.shared.config.max_workers() * CHUNKS_PER_WORKER).max(1);
+
+        let mut items = items.into_iter();
+        let chunks: Vec<Vec<_>> = std::iter::from_fn(|| {
+            let chunk: Vec<_> = items.by_ref().take(chunk_size).collect();
+            (!chunk.is_empty()).then_some(chunk)
+        }).collect();
+
+        let mut results: Vec<Option<R>> = chunks.iter().map(|_| None).collect();
+        let f = &f;
+
+        self.scope(|s| {
+            for (chunk, result) in chunks.into_iter().zip(results.iter_mut()) {
+                s.spawn(move || *result = Some(f(chunk)))?;
+            }
+            Ok(())
+        })?;
+
+        // The scope has waited for every chunk, but a discarded one left no result.
+        let results: Option<Vec<R>> = results.into_iter().collect();
+        results.ok_or(if self.shared.queue.is_closed() { ExecuteError::ShutDown } else { ExecuteError::Rejected })
+    }
+}
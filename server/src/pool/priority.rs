@@ -0,0 +1,95 @@
+use std::collections::VecDeque;
+
+use super::Job;
+
+/// How urgently a job should be picked up, for [`ThreadPool::execute_with_priority`](super::ThreadPool::execute_with_priority).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Low,
+}
+
+impl Priority {
+    pub(super) const COUNT: usize = 3;
+
+    pub(super) fn level(self) -> usize {
+        self as usize
+    }
+}
+
+/// After a waiting level has been passed over this many times in favour of
+/// a higher one, its next job goes first.
+pub(super) const STARVATION_LIMIT: usize = 8;
+
+/// Jobs submitted from outside the pool: one FIFO per priority, served
+/// highest priority first, with aging so lower levels are never starved.
+#[derive(Default)]
+pub(super) struct Injector {
+    levels: [VecDeque<Job>; Priority::COUNT],
+    passed_over: [usize; Priority::COUNT],
+}
+
+impl Injector {
+    pub(super) fn push(&mut self, job: Job) {
+        self.levels[job.priority.level()].push_back(job);
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.levels.iter().map(VecDeque::len).sum()
+    }
+
+    /// Returns a bit mask of the levels that have jobs waiting, bit 0 being [`Priority::High`].
+    pub(super) fn mask(&self) -> usize {
+        self.levels.iter().enumerate().filter(|(_, jobs)| !jobs.is_empty()).fold(0, |mask, (level, _)| mask | 1 << level)
+    }
+
+    pub(super) fn pop(&mut self) -> Option<Job> {
+        let starved = (0..Priority::COUNT)
+            .find(|&level| self.passed_over[level] >= STARVATION_LIMIT && !self.levels[level].is_empty());
+        let level = starved.or_else(|| (0..Priority::COUNT).find(|&level| !self.levels[level].is_empty()))?;
+
+        self.passed_over[level] = 0;
+        self.pass_over_below(level);
+
+        self.levels[level].pop_front()
+    }
+
+    /// Lets a job of `level` from elsewhere, such as a worker's own deque,
+    /// run ahead of the less urgent jobs waiting here, and counts that
+    /// against them as [`pop`](Self::pop) does. Returns `false` instead if one
+    /// of those levels is already starved, so that its job goes first.
+    pub(super) fn try_pass_over(&mut self, level: usize) -> bool {
+        let starved = (level + 1..Priority::COUNT)
+            .any(|lower| self.passed_over[lower] >= STARVATION_LIMIT && !self.levels[lower].is_empty());
+        if !starved {
+            self.pass_over_below(level);
+        }
+        !starved
+    }
+
+    fn pass_over_below(&mut self, level: usize) {
+        for lower in level + 1..Priority::COUNT {
+            if !self.levels[lower].is_empty() {
+                self.passed_over[lower] += 1;
+            }
+        }
+    }
+
+    /// Takes up to `count` more jobs of the given `level`, oldest first.
+    pub(super) fn take_level(&mut self, level: usize, count: usize) -> impl Iterator<Item = Job> + '_ {
+        let jobs = &mut self.levels[level];
+        let count = count.min(jobs.len());
+        jobs.drain(..count)
+    }
+
+    /// Removes the oldest job of the lowest priority that has any.
+    pub(super) fn pop_least_urgent(&mut self) -> Option<Job> {
+        self.levels.iter_mut().rev().find_map(VecDeque::pop_front)
+    }
+
+    pub(super) fn drain(&mut self) -> impl Iterator<Item = Job> + '_ {
+        self.levels.iter_mut().flat_map(|jobs| jobs.drain(..))
+    }
+}
@@ -0,0 +1,402 @@
+use std::{
+    cell::Cell,
+    collections::VecDeque,
+    error::Error,
+    fmt,
+    sync::{atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst}, Condvar, Mutex, MutexGuard, PoisonError},
+    thread,
+    time::Duration,
+};
+
+use super::{lock, priority::{Injector, Priority}, Job};
+
+/// Upper bound on how many jobs a worker moves from the injector to its own deque at once.
+const MAX_BATCH: usize = 32;
+
+thread_local! {
+    // The queue (by address) and slot of the pool worker running on this thread, if any.
+    static WORKER: Cell<Option<(usize, usize)>> = const { Cell::new(None) };
+}
+
+/// What [`ThreadPool::execute`](super::ThreadPool::execute) does when a bounded queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RejectionPolicy {
+    /// Wait until a worker frees a slot.
+    #[default]
+    Block,
+    /// Return [`ExecuteError::Rejected`] immediately.
+    Reject,
+    /// Discard the job that has been waiting longest among those of the
+    /// lowest priority to make room.
+    DropOldest,
+    /// Run the job on the thread that called `execute`.
+    CallerRuns,
+}
+
+/// Error returned when a job is not accepted by the pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecuteError {
+    /// The queue was full and the pool uses [`RejectionPolicy::Reject`], or
+    /// a job that a call waited on was evicted by
+    /// [`RejectionPolicy::DropOldest`].
+    Rejected,
+    /// The pool no longer accepts work.
+    ShutDown,
+    /// A thread the job needs, such as the timer thread for a scheduled job,
+    /// could not be spawned. The cause is logged.
+    SpawnFailed,
+}
+
+impl fmt::Display for ExecuteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecuteError::Rejected => write!(f, "job queue is full"),
+            ExecuteError::ShutDown => write!(f, "thread pool is shut down"),
+            ExecuteError::SpawnFailed => write!(f, "failed to spawn a thread for the job"),
+        }
+    }
+}
+
+impl Error for ExecuteError {}
+
+pub(super) enum Push {
+    Queued,
+    RunHere(Job),
+}
+
+pub(super) enum Pop {
+    Job(Job),
+    /// The worker found nothing to do for a whole keep-alive period.
+    Idle,
+    Closed,
+}
+
+/// Threads parked on one condition variable of the queue.
+///
+/// `waiting` counts parked threads and `tickets` counts notifications that no
+/// parked thread has consumed yet. Both only change under the sleep lock, so
+/// `waiting - tickets` is exactly the number of threads that are asleep and
+/// have nobody coming to wake them.
+struct Waiters {
+    waiting: AtomicUsize,
+    tickets: AtomicUsize,
+    condvar: Condvar,
+}
+
+impl Waiters {
+    fn new() -> Self {
+        Waiters { waiting: AtomicUsize::new(0), tickets: AtomicUsize::new(0), condvar: Condvar::new() }
+    }
+
+    fn unwoken(&self) -> usize {
+        self.waiting.load(SeqCst).saturating_sub(self.tickets.load(SeqCst))
+    }
+}
+
+/// Work-stealing job queue.
+///
+/// Jobs submitted from outside the pool go to a shared injector. Workers move
+/// small batches from the injector into their own deque, so the injector lock
+/// is taken once per batch rather than once per job, and idle workers steal
+/// from the deques of busy ones. Jobs of normal priority submitted from a
+/// worker thread go straight to that worker's deque.
+///
+/// The injector serves higher priorities first. A worker only keeps working
+/// through its own deque while nothing more urgent waits in the injector.
+///
+/// `len` counts every accepted job that has not been taken by a worker yet,
+/// and is what the capacity limit and the sleep/wake logic are based on.
+pub(super) struct JobQueue {
+    injector: Mutex<Injector>,
+    /// [`Injector::mask`] as of the last change, readable without the lock.
+    injector_levels: AtomicUsize,
+    locals: Box<[Mutex<VecDeque<Job>>]>,
+    len: AtomicUsize,
+    closed: AtomicBool,
+    sleep: Mutex<()>,
+    idle: Waiters,
+    blocked: Waiters,
+    capacity: Option<usize>,
+    policy: RejectionPolicy,
+}
+
+impl JobQueue {
+    pub(super) fn new(slots: usize, capacity: Option<usize>, policy: RejectionPolicy) -> Self {
+        JobQueue {
+            injector: Mutex::new(Injector::default()),
+            injector_levels: AtomicUsize::new(0),
+            locals: (0..slots).map(|_| Mutex::new(VecDeque::new())).collect(),
+            len: AtomicUsize::new(0),
+            closed: AtomicBool::new(false),
+            sleep: Mutex::new(()),
+            idle: Waiters::new(),
+            blocked: Waiters::new(),
+            capacity,
+            policy,
+        }
+    }
+
+    /// Marks the calling thread as the worker owning `slot`.
+    pub(super) fn register(&self, slot: usize) {
+        WORKER.with(|worker| worker.set(Some((self.id(), slot))));
+    }
+
+    fn id(&self) -> usize {
+        self as *const Self as usize
+    }
+
+    /// Returns the slot of the calling thread if it is one of this queue's workers.
+    pub(super) fn current_slot(&self) -> Option<usize> {
+        match WORKER.with(Cell::get) {
+            Some((queue, slot)) if queue == self.id() => Some(slot),
+            _ => None,
+        }
+    }
+
+    pub(super) fn push(&self, job: Job) -> Result<Push, ExecuteError> {
+        if self.closed.load(SeqCst) {
+            return Err(ExecuteError::ShutDown);
+        }
+
+        if let Some(capacity) = self.capacity {
+            while !self.reserve(capacity) {
+                match self.policy {
+                    RejectionPolicy::Block => self.wait_for_space(capacity)?,
+                    RejectionPolicy::Reject => return Err(ExecuteError::Rejected),
+                    RejectionPolicy::DropOldest => {
+                        if self.take_oldest().is_none() {
+                            // The slots are reserved by pushes that have not landed yet.
+                            thread::yield_now();
+                        }
+                    }
+                    RejectionPolicy::CallerRuns => return Ok(Push::RunHere(job)),
+                }
+            }
+        } else {
+            self.len.fetch_add(1, SeqCst);
+        }
+
+        match self.current_slot() {
+            Some(slot) if job.priority == Priority::Normal => lock(&self.locals[slot]).push_back(job),
+            _ => self.with_injector(|injector| injector.push(job)),
+        }
+        self.wake(&self.idle);
+
+        Ok(Push::Queued)
+    }
+
+    fn reserve(&self, capacity: usize) -> bool {
+        self.len.fetch_update(SeqCst, SeqCst, |len| (len < capacity).then_some(len + 1)).is_ok()
+    }
+
+    fn wait_for_space(&self, capacity: usize) -> Result<(), ExecuteError> {
+        let guard = lock(&self.sleep);
+        self.blocked.waiting.fetch_add(1, SeqCst);
+
+        if !self.closed.load(SeqCst) && self.len.load(SeqCst) >= capacity {
+            self.park(&self.blocked, guard, None);
+        } else {
+            self.blocked.waiting.fetch_sub(1, SeqCst);
+        }
+
+        if self.closed.load(SeqCst) {
+            Err(ExecuteError::ShutDown)
+        } else {
+            Ok(())
+        }
+    }
+
+    // Threads bump `waiting` and re-check `len` under the sleep lock before
+    // parking, and we look at `waiting` only after changing `len`, so either
+    // they see the change or we see them.
+    fn wake(&self, waiters: &Waiters) {
+        if waiters.unwoken() > 0 {
+            let _guard = lock(&self.sleep);
+
+            if waiters.unwoken() > 0 {
+                waiters.tickets.fetch_add(1, SeqCst);
+                waiters.condvar.notify_one();
+            }
+        }
+    }
+
+    /// Parks the caller, which has already counted itself in `waiters.waiting`.
+    /// Returns `true` if the wait timed out without a notification.
+    fn park(&self, waiters: &Waiters, guard: MutexGuard<'_, ()>, timeout: Option<Duration>) -> bool {
+        let (guard, timed_out) = match timeout {
+            Some(timeout) => {
+                let (guard, result) = waiters.condvar.wait_timeout(guard, timeout).unwrap_or_else(PoisonError::into_inner);
+                (guard, result.timed_out())
+            }
+            None => (waiters.condvar.wait(guard).unwrap_or_else(PoisonError::into_inner), false),
+        };
+
+        waiters.waiting.fetch_sub(1, SeqCst);
+        let notified = waiters.tickets.fetch_update(SeqCst, SeqCst, |tickets| tickets.checked_sub(1)).is_ok();
+        drop(guard);
+
+        timed_out && !notified
+    }
+
+    /// Returns the number of workers asleep with nobody coming to wake them.
+    pub(super) fn idle_workers(&self) -> usize {
+        self.idle.unwoken()
+    }
+
+    /// Removes the job that was submitted first, as far as the queue can tell.
+    fn take_oldest(&self) -> Option<Job> {
+        let job = self
+            .with_injector(Injector::pop_least_urgent)
+            .or_else(|| self.locals.iter().find_map(|local| lock(local).pop_front()))?;
+
+        self.len.fetch_sub(1, SeqCst);
+        Some(job)
+    }
+
+    /// Blocks until a job is available for the worker in `slot`.
+    ///
+    /// With a `keep_alive`, returns [`Pop::Idle`] once the worker has found
+    /// nothing to do for that long.
+    pub(super) fn pop(&self, slot: usize, keep_alive: Option<Duration>) -> Pop {
+        loop {
+            if let Some(job) = self.try_pop(slot) {
+                return Pop::Job(job);
+            }
+
+            let guard = lock(&self.sleep);
+            self.idle.waiting.fetch_add(1, SeqCst);
+
+            if self.len.load(SeqCst) == 0 && !self.closed.load(SeqCst) {
+                if self.park(&self.idle, guard, keep_alive) && self.len.load(SeqCst) == 0 {
+                    return Pop::Idle;
+                }
+                continue;
+            }
+
+            self.idle.waiting.fetch_sub(1, SeqCst);
+            drop(guard);
+
+            if self.closed.load(SeqCst) && self.len.load(SeqCst) == 0 {
+                return Pop::Closed;
+            }
+            // A job is counted but still on its way into a deque.
+            thread::yield_now();
+        }
+    }
+
+    /// Takes a job for the worker in `slot` without blocking.
+    pub(super) fn try_pop(&self, slot: usize) -> Option<Job> {
+        let job = self.find_job(slot)?;
+
+        self.len.fetch_sub(1, SeqCst);
+        self.wake(&self.blocked);
+        Some(job)
+    }
+
+    fn with_injector<R>(&self, f: impl FnOnce(&mut Injector) -> R) -> R {
+        let mut injector = lock(&self.injector);
+        let result = f(&mut injector);
+        self.injector_levels.store(injector.mask(), SeqCst);
+        result
+    }
+
+    fn find_job(&self, slot: usize) -> Option<Job> {
+        // Not holding the deque's lock while `local_goes_first` takes the
+        // injector's, which `take_batch` takes the other way round.
+        let front = lock(&self.locals[slot]).front().map(|job| job.priority.level());
+        if let Some(level) = front
+            && self.local_goes_first(level)
+            && let Some(job) = lock(&self.locals[slot]).pop_front()
+        {
+            return Some(job);
+        }
+
+        if let Some(job) = self.take_batch(slot) {
+            return Some(job);
+        }
+
+        // Steal from the back of another worker's deque, skipping any that are busy.
+        let count = self.locals.len();
+        (1..count).map(|offset| (slot + offset) % count).find_map(|victim| {
+            self.locals[victim].try_lock().ok().and_then(|mut local| local.pop_back())
+        })
+    }
+
+    /// Whether a job of `level` at the front of a worker's own deque should
+    /// run before anything in the injector.
+    fn local_goes_first(&self, level: usize) -> bool {
+        let levels = self.injector_levels.load(SeqCst);
+        if levels & ((1 << level) - 1) != 0 {
+            return false;
+        }
+        if levels >> (level + 1) == 0 {
+            return true;
+        }
+        // Less urgent jobs are waiting, which must not be passed over forever.
+        self.with_injector(|injector| injector.try_pass_over(level))
+    }
+
+    fn take_batch(&self, slot: usize) -> Option<Job> {
+        let mut injector = lock(&self.injector);
+        let job = injector.pop()?;
+
+        // Batch only while no other priority is waiting, so the injector
+        // keeps deciding the order whenever there is a choice to make.
+        // Anything already in the local deque was skipped for being less
+        // urgent, so the batch goes in front of it.
+        let level = job.priority.level();
+        let count = if injector.mask() == 1 << level {
+            (injector.len() / self.locals.len()).min(MAX_BATCH)
+        } else {
+            0
+        };
+        let batch: Vec<Job> = injector.take_level(level, count).collect();
+        let took_more = !batch.is_empty();
+
+        let mut local = lock(&self.locals[slot]);
+        for job in batch.into_iter().rev() {
+            local.push_front(job);
+        }
+        drop(local);
+
+        self.injector_levels.store(injector.mask(), SeqCst);
+        drop(injector);
+
+        if took_more {
+            // Let an idle worker steal part of what we just took.
+            self.wake(&self.idle);
+        }
+
+        Some(job)
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.len.load(SeqCst)
+    }
+
+    pub(super) fn is_closed(&self) -> bool {
+        self.closed.load(SeqCst)
+    }
+
+    pub(super) fn close(&self) {
+        self.closed.store(true, SeqCst);
+
+        let _guard = lock(&self.sleep);
+        self.idle.condvar.notify_all();
+        self.blocked.condvar.notify_all();
+    }
+
+    /// Closes the queue and discards every waiting job, returning how many were dropped.
+    pub(super) fn close_and_clear(&self) -> usize {
+        self.close();
+
+        let mut jobs: Vec<Job> = self.with_injector(|injector| injector.drain().collect());
+        for local in self.locals.iter() {
+            jobs.extend(lock(local).drain(..));
+        }
+        self.len.fetch_sub(jobs.len(), SeqCst);
+
+        // Dropped outside the locks: a job's captures may do arbitrary work when dropped.
+        jobs.len()
+    }
+}
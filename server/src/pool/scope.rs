@@ -0,0 +1,153 @@
+use std::{
+    any::Any,
+    marker::PhantomData,
+    mem,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Condvar, Mutex, PoisonError},
+    time::Duration,
+};
+
+use super::{lock, ExecuteError, ThreadPool};
+
+/// How often a worker waiting for its scope checks the queue for work to help with.
+const HELP_INTERVAL: Duration = Duration::from_millis(1);
+
+/// A scope for spawning jobs that borrow from the caller, created by [`ThreadPool::scope`].
+pub struct Scope<'scope, 'env: 'scope> {
+    pool: &'scope ThreadPool,
+    state: Arc<ScopeState>,
+    // Same variance as `std::thread::Scope`: invariant in both lifetimes.
+    scope: PhantomData<&'scope mut &'scope ()>,
+    env: PhantomData<&'env mut &'env ()>,
+}
+
+#[derive(Default)]
+struct ScopeState {
+    progress: Mutex<Progress>,
+    finished: Condvar,
+}
+
+#[derive(Default)]
+struct Progress {
+    pending: usize,
+    panic: Option<Box<dyn Any + Send>>,
+}
+
+impl ThreadPool {
+    /// Runs `f` with a [`Scope`] whose jobs may borrow anything that outlives
+    /// the call, like [`std::thread::scope`] but on the pool's workers.
+    ///
+    /// Returns once `f` and every job spawned in the scope have finished. If a
+    /// scoped job panicked, the panic is resumed here afterwards.
+    ///
+    /// ```
+    /// use server::ThreadPool;
+    ///
+    /// let pool = ThreadPool::new(4);
+    /// let mut totals = vec![0; 4];
+    /// let data: Vec<u64> = (1..=100).collect();
+    ///
+    /// pool.scope(|s| {
+    ///     for (chunk, total) in data.chunks(25).zip(totals.iter_mut()) {
+    ///         s.spawn(move || *total = chunk.iter().sum()).unwrap();
+    ///     }
+    /// });
+    ///
+    /// assert_eq!(totals.iter().sum::<u64>(), 5050);
+    /// ```
+    pub fn scope<'env, F, T>(&self, f: F) -> T
+    where F: for<'scope> FnOnce(&'scope Scope<'scope, 'env>) -> T {
+        let scope = Scope { pool: self, state: Arc::default(), scope: PhantomData, env: PhantomData };
+
+        // Even if `f` panics, jobs that borrow from the caller must finish
+        // before the borrowed data can go away.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(&scope)));
+        let job_panic = scope.wait();
+
+        match (result, job_panic) {
+            (Err(payload), _) | (Ok(_), Some(payload)) => panic::resume_unwind(payload),
+            (Ok(value), None) => value,
+        }
+    }
+}
+
+impl<'scope> Scope<'scope, '_> {
+    /// Queues `f` on the pool. `f` may borrow anything that outlives the scope.
+    pub fn spawn<F>(&'scope self, f: F) -> Result<(), ExecuteError>
+    where F: FnOnce() + Send + 'scope {
+        lock(&self.state.progress).pending += 1;
+
+        let job = ScopedJob { f: Some(f), state: Arc::clone(&self.state) };
+        let job: Box<dyn FnOnce() + Send + 'scope> = Box::new(move || job.run());
+
+        // SAFETY: `ThreadPool::scope` does not return before `pending` is
+        // back to zero, and `ScopedJob` only decrements it after `f` has been
+        // run or dropped. So `f` and everything it borrows are gone before
+        // 'scope ends, whether the job runs, panics, or is discarded.
+        let job = unsafe {
+            mem::transmute::<Box<dyn FnOnce() + Send + 'scope>, Box<dyn FnOnce() + Send + 'static>>(job)
+        };
+
+        self.pool.execute(job)
+    }
+
+    /// Blocks until every job has finished and returns the first panic among them.
+    fn wait(&self) -> Option<Box<dyn Any + Send>> {
+        let shared = &self.pool.shared;
+        // A worker waiting here would otherwise hold up the jobs it waits for.
+        let helper = shared.queue.current_slot();
+        let mut progress = lock(&self.state.progress);
+
+        while progress.pending > 0 {
+            let Some(slot) = helper else {
+                progress = self.state.finished.wait(progress).unwrap_or_else(PoisonError::into_inner);
+                continue;
+            };
+
+            drop(progress);
+            if let Some(job) = shared.queue.try_pop(slot) {
+                shared.run_job(slot, job);
+                progress = lock(&self.state.progress);
+                continue;
+            }
+
+            progress = lock(&self.state.progress);
+            if progress.pending > 0 {
+                progress = self.state.finished.wait_timeout(progress, HELP_INTERVAL)
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .0;
+            }
+        }
+
+        progress.panic.take()
+    }
+}
+
+struct ScopedJob<F> {
+    f: Option<F>,
+    state: Arc<ScopeState>,
+}
+
+impl<F: FnOnce()> ScopedJob<F> {
+    fn run(mut self) {
+        let f = self.f.take().unwrap();
+
+        if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(f)) {
+            lock(&self.state.progress).panic.get_or_insert(payload);
+        }
+    }
+}
+
+impl<F> Drop for ScopedJob<F> {
+    fn drop(&mut self) {
+        // Drop the closure first if it never ran, so nothing it borrows is
+        // touched after the scope is told it may end.
+        drop(self.f.take());
+
+        let mut progress = lock(&self.state.progress);
+        progress.pending -= 1;
+        if progress.pending == 0 {
+            self.state.finished.notify_all();
+        }
+    }
+}
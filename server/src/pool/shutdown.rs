@@ -0,0 +1,17 @@
+/// Outcome of [`ThreadPool::shutdown`](super::ThreadPool::shutdown) and
+/// [`ThreadPool::shutdown_now`](super::ThreadPool::shutdown_now).
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ShutdownReport {
+    /// Queued jobs that were thrown away without running.
+    pub discarded_jobs: usize,
+    /// Ids of workers still busy when the timeout expired. Their threads are
+    /// detached and exit on their own once the current job returns.
+    pub unfinished: Vec<usize>,
+}
+
+impl ShutdownReport {
+    /// Returns `true` if every worker exited before the timeout.
+    pub fn is_complete(&self) -> bool {
+        self.unfinished.is_empty()
+    }
+}
@@ -0,0 +1,259 @@
+use std::{
+    cmp::Ordering,
+    collections::BinaryHeap,
+    sync::{atomic::{AtomicBool, Ordering::SeqCst}, Arc, Condvar, Mutex, PoisonError, Weak},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::log::Level;
+
+use super::{lock, worker::Shared, ExecuteError, Priority, ThreadPool};
+
+/// Cancels a job scheduled with [`ThreadPool::schedule_after`] or
+/// [`ThreadPool::schedule_at_fixed_rate`].
+///
+/// Dropping the handle does not cancel the job.
+#[derive(Debug, Clone)]
+pub struct ScheduledHandle {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl ScheduledHandle {
+    /// Stops the job from being queued again. A run that is already queued
+    /// or executing is not interrupted.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(SeqCst)
+    }
+}
+
+impl ThreadPool {
+    /// Queues `f` on the pool once `delay` has passed.
+    ///
+    /// Scheduled jobs are kept by a timer thread that the pool starts the
+    /// first time a job is scheduled. Fails with [`ExecuteError::SpawnFailed`]
+    /// if that thread cannot be spawned.
+    pub fn schedule_after<F>(&self, delay: Duration, f: F) -> Result<ScheduledHandle, ExecuteError>
+    where F: FnOnce() + Send + 'static {
+        self.schedule(Instant::now() + delay, Task::Once(Some(Box::new(f))))
+    }
+
+    /// Queues `f` on the pool every `period`, starting one period from now.
+    ///
+    /// Runs are planned at fixed points in time rather than relative to the
+    /// previous run. If a run is still going when the next one is due, that
+    /// tick is skipped, so `f` never runs concurrently with itself.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `period` is zero.
+    pub fn schedule_at_fixed_rate<F>(&self, period: Duration, f: F) -> Result<ScheduledHandle, ExecuteError>
+    where F: Fn() + Send + Sync + 'static {
+        assert!(!period.is_zero(), "schedule_at_fixed_rate period must be non-zero");
+
+        let task = Task::Periodic { run: Arc::new(f), period, running: Arc::new(AtomicBool::new(false)) };
+        self.schedule(Instant::now() + period, task)
+    }
+
+    fn schedule(&self, deadline: Instant, task: Task) -> Result<ScheduledHandle, ExecuteError> {
+        let mut timer = lock(&self.timer);
+
+        if self.shared.queue.is_closed() {
+            return Err(ExecuteError::ShutDown);
+        }
+
+        let timer = match &mut *timer {
+            Some(timer) => timer,
+            None => {
+                let builder = thread::Builder::new().name(format!("{}-timer", self.shared.config.name_prefix));
+                match Timer::start(builder, Arc::downgrade(&self.shared)) {
+                    Ok(started) => timer.insert(started),
+                    Err(e) => {
+                        self.shared.config.logger.log(Level::Error, format_args!("Failed to spawn the timer thread: {e}"));
+                        return Err(ExecuteError::SpawnFailed);
+                    }
+                }
+            }
+        };
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        timer.insert(Entry { deadline, seq: 0, task, cancelled: Arc::clone(&cancelled) });
+
+        Ok(ScheduledHandle { cancelled })
+    }
+
+    /// Stops the timer thread and forgets every scheduled job.
+    pub(super) fn stop_timer(&self) {
+        if let Some(timer) = lock(&self.timer).take() {
+            timer.stop();
+        }
+    }
+}
+
+enum Task {
+    Once(Option<Box<dyn FnOnce() + Send + 'static>>),
+    Periodic {
+        run: Arc<dyn Fn() + Send + Sync + 'static>,
+        period: Duration,
+        running: Arc<AtomicBool>,
+    },
+}
+
+struct Entry {
+    deadline: Instant,
+    /// Breaks ties between equal deadlines in scheduling order.
+    seq: u64,
+    task: Task,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Entry {}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Entry {
+    // Reversed, so that `BinaryHeap` pops the earliest deadline first.
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.deadline, other.seq).cmp(&(self.deadline, self.seq))
+    }
+}
+
+#[derive(Default)]
+struct TimerState {
+    entries: BinaryHeap<Entry>,
+    next_seq: u64,
+    stopped: bool,
+}
+
+#[derive(Default)]
+struct TimerShared {
+    state: Mutex<TimerState>,
+    changed: Condvar,
+}
+
+/// A thread that sleeps until the next scheduled job is due and submits it to the pool.
+pub(super) struct Timer {
+    shared: Arc<TimerShared>,
+    thread: thread::JoinHandle<()>,
+}
+
+impl Timer {
+    fn start(builder: thread::Builder, pool: Weak<Shared>) -> std::io::Result<Self> {
+        let shared = Arc::new(TimerShared::default());
+        let state = Arc::clone(&shared);
+        let thread = builder.spawn(move || run(&state, &pool))?;
+
+        Ok(Timer { shared, thread })
+    }
+
+    fn insert(&self, mut entry: Entry) {
+        let mut state = lock(&self.shared.state);
+
+        entry.seq = state.next_seq;
+        state.next_seq += 1;
+        state.entries.push(entry);
+
+        self.shared.changed.notify_one();
+    }
+
+    fn stop(self) {
+        lock(&self.shared.state).stopped = true;
+        self.shared.changed.notify_one();
+
+        let _ = self.thread.join();
+    }
+}
+
+fn run(timer: &TimerShared, pool: &Weak<Shared>) {
+    loop {
+        let Some(mut entry) = next_due(timer) else {
+            return;
+        };
+        if entry.cancelled.load(SeqCst) {
+            continue;
+        }
+        let Some(pool) = pool.upgrade() else {
+            return;
+        };
+
+        match &mut entry.task {
+            Task::Once(run) => {
+                if let Some(run) = run.take() {
+                    submit(&pool, run);
+                }
+            }
+            Task::Periodic { run, period, running } => {
+                if running.swap(true, SeqCst) {
+                    pool.config.logger.log(Level::Warn, format_args!("Skipping a periodic job that is still running."));
+                } else {
+                    let run = Arc::clone(run);
+                    let running = Arc::clone(running);
+                    submit(&pool, Box::new(move || {
+                        let _running = RunningFlag(running);
+                        run();
+                    }));
+                }
+
+                let now = Instant::now();
+                while entry.deadline <= now {
+                    entry.deadline += *period;
+                }
+
+                let mut state = lock(&timer.state);
+                entry.seq = state.next_seq;
+                state.next_seq += 1;
+                state.entries.push(entry);
+            }
+        }
+    }
+}
+
+/// Waits for the earliest entry to become due and removes it. Returns `None` once stopped.
+fn next_due(timer: &TimerShared) -> Option<Entry> {
+    let mut state = lock(&timer.state);
+
+    loop {
+        if state.stopped {
+            return None;
+        }
+
+        let now = Instant::now();
+        state = match state.entries.peek() {
+            Some(entry) if entry.deadline <= now => return state.entries.pop(),
+            Some(entry) => {
+                let timeout = entry.deadline - now;
+                timer.changed.wait_timeout(state, timeout).unwrap_or_else(PoisonError::into_inner).0
+            }
+            None => timer.changed.wait(state).unwrap_or_else(PoisonError::into_inner),
+        };
+    }
+}
+
+fn submit(pool: &Arc<Shared>, run: Box<dyn FnOnce() + Send + 'static>) {
+    if let Err(e) = pool.submit(run, Priority::Normal) {
+        pool.config.logger.log(Level::Warn, format_args!("Dropping a scheduled job: {e}"));
+    }
+}
+
+/// Clears a periodic job's running flag when its run ends, even by panicking.
+struct RunningFlag(Arc<AtomicBool>);
+
+impl Drop for RunningFlag {
+    fn drop(&mut self) {
+        self.0.store(false, SeqCst);
+    }
+}
@@ -0,0 +1,251 @@
+use std::{
+    io,
+    panic::{self, AssertUnwindSafe},
+    sync::{atomic::{AtomicUsize, Ordering::SeqCst}, Arc, Condvar, Mutex, PoisonError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::log::Level;
+
+use super::{lock, panic_message, queue::{JobQueue, Pop, Push}, Builder, ExecuteError, Job, PoolEvent, Priority};
+
+/// State shared between a [`ThreadPool`](super::ThreadPool) and its workers.
+pub(super) struct Shared {
+    pub(super) queue: JobQueue,
+    pub(super) config: Builder,
+    slots: Mutex<Vec<Slot>>,
+    exited: Condvar,
+    /// Workers that are running and not retiring. Only changed under `slots`.
+    size: AtomicUsize,
+}
+
+/// A worker id, and the thread that currently owns it.
+#[derive(Default)]
+struct Slot {
+    thread: Option<thread::JoinHandle<()>>,
+    retired: bool,
+    exited: bool,
+}
+
+impl Slot {
+    fn is_free(&self) -> bool {
+        self.thread.is_none() || self.exited
+    }
+}
+
+impl Shared {
+    pub(super) fn new(config: Builder) -> Self {
+        Shared {
+            queue: JobQueue::new(config.max_workers(), config.queue_capacity, config.rejection_policy),
+            slots: Mutex::new((0..config.max_workers()).map(|_| Slot::default()).collect()),
+            exited: Condvar::new(),
+            size: AtomicUsize::new(0),
+            config,
+        }
+    }
+
+    pub(super) fn size(&self) -> usize {
+        self.size.load(SeqCst)
+    }
+
+    /// Starts a worker in a free slot and returns its id, or `None` if the pool is at its maximum size.
+    pub(super) fn spawn_worker(self: &Arc<Self>) -> io::Result<Option<usize>> {
+        let mut slots = lock(&self.slots);
+
+        if self.size() >= self.config.max_workers() {
+            return Ok(None);
+        }
+        let Some(id) = slots.iter().position(Slot::is_free) else {
+            return Ok(None);
+        };
+
+        // A previous owner of the slot has already exited; this does not block.
+        if let Some(old) = slots[id].thread.take() {
+            let _ = old.join();
+        }
+
+        let shared = Arc::clone(self);
+        let thread = self.config.thread_builder(id).spawn(move || shared.run(id))?;
+
+        slots[id] = Slot { thread: Some(thread), retired: false, exited: false };
+        self.size.fetch_add(1, SeqCst);
+
+        Ok(Some(id))
+    }
+
+    /// Queues `run`, as described on [`ThreadPool::execute`](super::ThreadPool::execute).
+    pub(super) fn submit(
+        self: &Arc<Self>,
+        run: Box<dyn FnOnce() + Send + 'static>,
+        priority: Priority,
+    ) -> Result<(), ExecuteError> {
+        // Clock reads are only worth their cost when someone is watching.
+        let queued_at = self.config.observer.is_some().then(Instant::now);
+        let job = Job { run, priority, queued_at };
+
+        let push = self.queue.push(job)?;
+        self.notify(&PoolEvent::Submitted);
+
+        match push {
+            Push::Queued => self.grow_if_backed_up(),
+            Push::RunHere(job) => (job.run)(),
+        }
+
+        Ok(())
+    }
+
+    /// Adds a worker if jobs are piling up and nobody is asleep to take them.
+    fn grow_if_backed_up(self: &Arc<Self>) {
+        if self.size() < self.config.max_workers()
+            && self.queue.len() > self.queue.idle_workers()
+            && let Err(e) = self.spawn_worker()
+        {
+            self.config.logger.log(Level::Error, format_args!("Failed to spawn an extra worker: {e}"));
+        }
+    }
+
+    /// Passes `event` to the observer, if any. A panicking observer is ignored.
+    pub(super) fn notify(&self, event: &PoolEvent<'_>) {
+        if let Some(observer) = &self.config.observer {
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| observer.on_event(event)));
+        }
+    }
+
+    fn run(&self, id: usize) {
+        let _exit = ExitNotice { shared: self, id };
+        let keep_alive = (self.config.max_workers() > self.config.size).then_some(self.config.keep_alive);
+
+        let logger = &self.config.logger;
+
+        self.queue.register(id);
+        logger.log(Level::Debug, format_args!("Worker {id} started."));
+
+        loop {
+            match self.queue.pop(id, keep_alive) {
+                Pop::Job(job) => self.run_job(id, job),
+                Pop::Idle => {
+                    if self.try_retire(id) {
+                        logger.log(Level::Debug, format_args!("Worker {id} idle; retiring."));
+                        break;
+                    }
+                }
+                Pop::Closed => {
+                    logger.log(Level::Debug, format_args!("Worker {id} disconnected; shutting down."));
+                    break;
+                }
+            }
+        }
+    }
+
+    pub(super) fn run_job(&self, id: usize, job: Job) {
+        let started = job.queued_at.map(|queued_at| {
+            let now = Instant::now();
+            self.notify(&PoolEvent::Started { worker: id, queued_for: now.saturating_duration_since(queued_at) });
+            now
+        });
+
+        let result = panic::catch_unwind(AssertUnwindSafe(job.run));
+        let ran_for = started.map_or(Duration::ZERO, |started| started.elapsed());
+
+        match result {
+            Ok(()) => self.notify(&PoolEvent::Completed { worker: id, ran_for }),
+            Err(payload) => {
+                let message = panic_message(&*payload);
+                self.config.logger.log(Level::Warn, format_args!("Worker {id} job panicked: {message}; continuing."));
+
+                self.notify(&PoolEvent::Panicked { worker: id, ran_for, payload: &*payload });
+
+                if let Some(handler) = &self.config.panic_handler {
+                    // A panicking handler must not take the worker down either.
+                    let _ = panic::catch_unwind(AssertUnwindSafe(|| handler(id, &*payload)));
+                }
+            }
+        }
+    }
+
+    fn try_retire(&self, id: usize) -> bool {
+        let mut slots = lock(&self.slots);
+
+        if self.size() <= self.config.size {
+            return false;
+        }
+        self.size.fetch_sub(1, SeqCst);
+
+        // `execute` bumps the queue length before checking the size, so
+        // either it saw us leave and grows the pool, or we see its job here.
+        if self.queue.len() > 0 {
+            self.size.fetch_add(1, SeqCst);
+            return false;
+        }
+
+        slots[id].retired = true;
+        true
+    }
+
+    /// Waits until every worker has exited, or until `deadline`, and joins
+    /// the ones that did. Returns the ids of the workers still running; their
+    /// threads are detached.
+    ///
+    /// Called from one of the pool's own workers, that worker is left out.
+    pub(super) fn join_workers(&self, deadline: Option<Instant>) -> Vec<usize> {
+        let current = self.queue.current_slot();
+        let running = |slots: &[Slot]| {
+            slots.iter().enumerate().any(|(id, slot)| !slot.is_free() && Some(id) != current)
+        };
+
+        let mut slots = lock(&self.slots);
+
+        while running(&slots) {
+            slots = match deadline {
+                Some(deadline) => {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    if remaining.is_zero() {
+                        break;
+                    }
+                    self.exited.wait_timeout(slots, remaining).unwrap_or_else(PoisonError::into_inner).0
+                }
+                None => self.exited.wait(slots).unwrap_or_else(PoisonError::into_inner),
+            };
+        }
+
+        let mut finished = Vec::new();
+        let mut unfinished = Vec::new();
+
+        for (id, slot) in slots.iter_mut().enumerate() {
+            if slot.exited {
+                finished.extend(slot.thread.take());
+            } else if slot.thread.is_some() && Some(id) != current {
+                slot.thread = None;
+                unfinished.push(id);
+            }
+        }
+        drop(slots);
+
+        for thread in finished {
+            let _ = thread.join();
+        }
+
+        unfinished
+    }
+}
+
+/// Records that a worker thread has exited, however it got there.
+struct ExitNotice<'a> {
+    shared: &'a Shared,
+    id: usize,
+}
+
+impl Drop for ExitNotice<'_> {
+    fn drop(&mut self) {
+        let mut slots = lock(&self.shared.slots);
+        let slot = &mut slots[self.id];
+
+        if !slot.retired {
+            self.shared.size.fetch_sub(1, SeqCst);
+        }
+        slot.exited = true;
+
+        self.shared.exited.notify_all();
+    }
+}
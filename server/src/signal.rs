@@ -0,0 +1,53 @@
+use std::{
+    io,
+    sync::atomic::{AtomicBool, Ordering::SeqCst},
+};
+
+static RECEIVED: AtomicBool = AtomicBool::new(false);
+
+/// Catches SIGINT and SIGTERM, so that [`shutdown_requested`] can tell the
+/// server to stop. A second signal ends the process right away.
+///
+/// Does nothing on platforms other than Unix.
+pub fn install_shutdown_handler() -> io::Result<()> {
+    #[cfg(unix)]
+    unix::install()?;
+    Ok(())
+}
+
+/// Returns whether SIGINT or SIGTERM has been received.
+pub fn shutdown_requested() -> bool {
+    RECEIVED.load(SeqCst)
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::{ffi::c_int, io, sync::atomic::Ordering::SeqCst};
+
+    const SIGINT: c_int = 2;
+    const SIGTERM: c_int = 15;
+    const SIG_ERR: usize = usize::MAX;
+
+    unsafe extern "C" {
+        fn signal(signum: c_int, handler: extern "C" fn(c_int)) -> usize;
+        fn _exit(status: c_int) -> !;
+    }
+
+    // Only async-signal-safe work here: an atomic swap and `_exit`.
+    extern "C" fn on_signal(signum: c_int) {
+        if super::RECEIVED.swap(true, SeqCst) {
+            // SAFETY: `_exit` is async-signal-safe and ends the process.
+            unsafe { _exit(128 + signum) }
+        }
+    }
+
+    pub(super) fn install() -> io::Result<()> {
+        for signum in [SIGINT, SIGTERM] {
+            // SAFETY: `on_signal` only touches an atomic and calls `_exit`.
+            if unsafe { signal(signum, on_signal) } == SIG_ERR {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,201 @@
+//! An example JSON resource: a to-do list kept in memory.
+//!
+//! | Request                  | Response                                   |
+//! |--------------------------|--------------------------------------------|
+//! | `GET /api/todos`         | 200 with every to-do, oldest first         |
+//! | `POST /api/todos`        | 201 with the new to-do and its `Location`  |
+//! | `GET /api/todos/:id`     | 200 with the to-do                         |
+//! | `PUT /api/todos/:id`     | 200 with the replaced to-do                |
+//! | `DELETE /api/todos/:id`  | 204                                        |
+//!
+//! `POST` and `PUT` take `{"title": "...", "done": false}`, where `done` may
+//! be left out. Unknown ids get a 404 response, bodies that are not JSON a
+//! 415 one and JSON of the wrong shape a 422 one.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use server::http::{Request, Response, Router, StatusCode};
+use server::json::{FromJson, JsonError, ToJson, Value};
+
+const PATH: &str = "/api/todos";
+
+#[derive(Debug, Clone, PartialEq)]
+struct Todo {
+    id: u64,
+    title: String,
+    done: bool,
+}
+
+impl ToJson for Todo {
+    fn to_json(&self) -> Value {
+        Value::Object(vec![
+            ("id".to_string(), self.id.to_json()),
+            ("title".to_string(), self.title.to_json()),
+            ("done".to_string(), self.done.to_json()),
+        ])
+    }
+}
+
+/// The body of a `POST` or `PUT` request.
+struct TodoInput {
+    title: String,
+    done: bool,
+}
+
+impl FromJson for TodoInput {
+    fn from_json(value: &Value) -> Result<Self, JsonError> {
+        if value.as_object().is_none() {
+            return Err(JsonError::new("expected an object"));
+        }
+        let title = String::from_json(value.get("title").ok_or_else(|| JsonError::new("missing field `title`"))?)
+            .map_err(|_| JsonError::new("`title` must be a string"))?;
+        if title.trim().is_empty() {
+            return Err(JsonError::new("`title` must not be empty"));
+        }
+        let done = match value.get("done") {
+            Some(done) => bool::from_json(done).map_err(|_| JsonError::new("`done` must be a boolean"))?,
+            None => false,
+        };
+        Ok(TodoInput { title, done })
+    }
+}
+
+#[derive(Default)]
+struct Store {
+    next_id: u64,
+    todos: BTreeMap<u64, Todo>,
+}
+
+/// Adds the `/api/todos` routes to `router`, backed by an empty list.
+pub fn register(router: &mut Router) {
+    let store = Arc::new(Mutex::new(Store::default()));
+
+    let list = Arc::clone(&store);
+    let create = Arc::clone(&store);
+    let show = Arc::clone(&store);
+    let replace = Arc::clone(&store);
+    let remove = store;
+
+    router
+        .get(PATH, move |_: &Request| {
+            let store = list.lock().unwrap_or_else(PoisonError::into_inner);
+            let todos: Vec<&Todo> = store.todos.values().collect();
+            Response::json(StatusCode::Ok, &todos)
+        })
+        .post(PATH, move |req: &Request| {
+            let input = match req.json::<TodoInput>() {
+                Ok(input) => input,
+                Err(rejection) => return rejection.into(),
+            };
+            let mut store = create.lock().unwrap_or_else(PoisonError::into_inner);
+            store.next_id += 1;
+            let todo = Todo { id: store.next_id, title: input.title, done: input.done };
+            store.todos.insert(todo.id, todo.clone());
+            Response::json(StatusCode::Created, &todo).header("Location", format!("{PATH}/{}", todo.id))
+        })
+        .get(&format!("{PATH}/:id"), move |req: &Request| {
+            let store = show.lock().unwrap_or_else(PoisonError::into_inner);
+            match id(req).and_then(|id| store.todos.get(&id)) {
+                Some(todo) => Response::json(StatusCode::Ok, todo),
+                None => not_found(req),
+            }
+        })
+        .put(&format!("{PATH}/:id"), move |req: &Request| {
+            let input = match req.json::<TodoInput>() {
+                Ok(input) => input,
+                Err(rejection) => return rejection.into(),
+            };
+            let mut store = replace.lock().unwrap_or_else(PoisonError::into_inner);
+            match id(req).and_then(|id| store.todos.get_mut(&id)) {
+                Some(todo) => {
+                    todo.title = input.title;
+                    todo.done = input.done;
+                    Response::json(StatusCode::Ok, todo)
+                }
+                None => not_found(req),
+            }
+        })
+        .delete(&format!("{PATH}/:id"), move |req: &Request| {
+            let mut store = remove.lock().unwrap_or_else(PoisonError::into_inner);
+            match id(req).and_then(|id| store.todos.remove(&id)) {
+                Some(_) => Response::new(StatusCode::NoContent),
+                None => not_found(req),
+            }
+        });
+}
+
+/// The `:id` of the request, if it is a number.
+fn id(req: &Request) -> Option<u64> {
+    req.param("id")?.parse().ok()
+}
+
+fn not_found(req: &Request) -> Response {
+    let message = format!("no to-do at {}", req.path());
+    Response::json(StatusCode::NotFound, &Value::Object(vec![("error".to_string(), Value::String(message))]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn send(router: &Router, method: &str, target: &str, body: Option<&str>) -> Response {
+        let raw = match body {
+            Some(body) => format!(
+                "{method} {target} HTTP/1.1\r\nHost: localhost\r\n\
+                 Content-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+                body.len()
+            ),
+            None => format!("{method} {target} HTTP/1.1\r\nHost: localhost\r\n\r\n"),
+        };
+        let mut request = Request::read_from(&mut raw.as_bytes()).unwrap();
+        router.dispatch(&mut request)
+    }
+
+    fn body(response: &Response) -> &str {
+        std::str::from_utf8(response.body()).unwrap()
+    }
+
+    #[test]
+    fn creates_reads_replaces_and_deletes() {
+        let mut router = Router::new();
+        register(&mut router);
+
+        let created = send(&router, "POST", "/api/todos", Some(r#"{"title":"Write tests"}"#));
+        assert_eq!(created.status(), StatusCode::Created);
+        assert_eq!(created.headers().get("Location"), Some("/api/todos/1"));
+        assert_eq!(created.headers().get("Content-Type"), Some("application/json"));
+        assert_eq!(body(&created), r#"{"id":1,"title":"Write tests","done":false}"#);
+
+        let replaced = send(&router, "PUT", "/api/todos/1", Some(r#"{"title":"Write tests","done":true}"#));
+        assert_eq!(body(&replaced), r#"{"id":1,"title":"Write tests","done":true}"#);
+        send(&router, "POST", "/api/todos", Some(r#"{"title":"Ship it"}"#));
+        assert_eq!(
+            body(&send(&router, "GET", "/api/todos", None)),
+            r#"[{"id":1,"title":"Write tests","done":true},{"id":2,"title":"Ship it","done":false}]"#
+        );
+
+        assert_eq!(send(&router, "DELETE", "/api/todos/1", None).status(), StatusCode::NoContent);
+        assert_eq!(send(&router, "GET", "/api/todos/1", None).status(), StatusCode::NotFound);
+        assert_eq!(send(&router, "DELETE", "/api/todos/1", None).status(), StatusCode::NotFound);
+        assert_eq!(send(&router, "GET", "/api/todos/two", None).status(), StatusCode::NotFound);
+        assert_eq!(body(&send(&router, "GET", "/api/todos/2", None)), r#"{"id":2,"title":"Ship it","done":false}"#);
+    }
+
+    #[test]
+    fn rejects_bad_bodies() {
+        let mut router = Router::new();
+        register(&mut router);
+
+        let raw = "POST /api/todos HTTP/1.1\r\nHost: localhost\r\nContent-Type: text/plain\r\nContent-Length: 2\r\n\r\n{}";
+        let mut request = Request::read_from(&mut raw.as_bytes()).unwrap();
+        assert_eq!(router.dispatch(&mut request).status(), StatusCode::UnsupportedMediaType);
+
+        for payload in ["{", "[]", "{}", r#"{"title":""}"#, r#"{"title":"x","done":"yes"}"#] {
+            let response = send(&router, "POST", "/api/todos", Some(payload));
+            assert_eq!(response.status(), StatusCode::UnprocessableEntity, "{payload}");
+        }
+        assert_eq!(send(&router, "PUT", "/api/todos/1", Some(r#"{"title":"x"}"#)).status(), StatusCode::NotFound);
+        assert_eq!(body(&send(&router, "GET", "/api/todos", None)), "[]");
+    }
+}